@@ -0,0 +1,42 @@
+//! Runs the flow with the async engine on a tokio runtime.
+//!
+//! Run with `cargo run --example async_flow`.
+use payments::flow::{process_csv_transactions_async, process_json_lines_transactions_async};
+use payments::{Engine, FlowConfig, InMemoryDB};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::builder().build_async(InMemoryDB::new());
+    let config = FlowConfig::default();
+
+    // Inputs of different clients are applied concurrently,
+    // each one is read on a blocking thread of the runtime.
+    let csv = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+withdrawal, 1, 2, 4.0
+";
+    let json_lines = r#"{"type": "deposit", "client": 2, "tx": 3, "amount": "7.5"}
+{"type": "dispute", "client": 2, "tx": 3}
+"#;
+    let (csv_summary, json_summary) = tokio::join!(
+        process_csv_transactions_async(&engine, &config, csv.as_bytes()),
+        process_json_lines_transactions_async(&engine, &config, json_lines.as_bytes()),
+    );
+    for summary in [csv_summary?, json_summary?] {
+        println!(
+            "{} rows, {} applied, {} rejected",
+            summary.rows, summary.applied, summary.rejected
+        );
+    }
+
+    let mut clients = engine.get_all_clients().await?;
+    clients.sort_by_key(|client| client.client);
+    for client in clients {
+        println!(
+            "client {}: available {}, held {}, total {}, locked {}",
+            client.client, client.available, client.held, client.total, client.locked
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! Embeds the engine into another program.
+//!
+//! Run with `cargo run --example embedded`.
+use payments::{amount, process_csv_transactions, Engine, FlowConfig, Outcome, Transaction};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Engine::new();
+
+    // Transactions built in code are applied one by one.
+    let transactions = [
+        Transaction::deposit(1, 1, amount!(10.0)),
+        Transaction::withdrawal(1, 2, amount!(25.0)),
+        Transaction::dispute(1, 1),
+        Transaction::resolve(1, 1),
+    ];
+    for tx in &transactions {
+        match engine.process_transaction(tx)? {
+            Outcome::Applied => println!("{} {}: applied", tx.type_str(), tx.id()),
+            Outcome::Rejected(rejection) => {
+                println!("{} {}: rejected, {rejection}", tx.type_str(), tx.id())
+            }
+        }
+    }
+
+    // Whole inputs are applied by the flow.
+    let input = "\
+type, client, tx, amount
+deposit, 2, 3, 5.0
+withdrawal, 2, 4, 1.5
+";
+    let summary = process_csv_transactions(&engine, &FlowConfig::default(), input.as_bytes())?;
+    println!(
+        "{} rows, {} applied, {} rejected",
+        summary.rows, summary.applied, summary.rejected
+    );
+
+    let mut clients = engine.get_all_clients()?;
+    clients.sort_by_key(|client| client.client);
+    for client in clients {
+        println!(
+            "client {}: available {}, held {}, total {}, locked {}",
+            client.client, client.available, client.held, client.total, client.locked
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,17 @@
+//! Arbitrary CSV input through the flow, the engine and the CSV output of client balances.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use payments::output::{write_records, OutputFormat};
+use payments::{process_csv_transactions, Engine, FlowConfig};
+
+fuzz_target!(|data: &[u8]| {
+    let engine = Engine::new();
+    // Malformed rows are errors, transactions before them stay applied.
+    let _ = process_csv_transactions(&engine, &FlowConfig::default(), data);
+    let clients = engine.get_all_clients().unwrap();
+    for client in &clients {
+        assert_eq!(client.available.checked_add(client.held), Ok(client.total));
+    }
+    let mut output = vec![];
+    write_records(clients, &mut output, OutputFormat::Csv).unwrap();
+});
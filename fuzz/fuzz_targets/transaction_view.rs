@@ -0,0 +1,21 @@
+//! Arbitrary CSV and JSON Lines rows through `TransactionView` and `Transaction::try_from`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use payments::input::{read_csv, read_json_lines};
+use payments::{Transaction, TransactionView};
+
+fuzz_target!(|data: &[u8]| {
+    for record in read_csv(data).chain(read_json_lines(data)) {
+        let Ok(view) = record else {
+            continue;
+        };
+        let (client, tx) = (view.client, view.tx);
+        let Ok(transaction) = Transaction::try_from(view) else {
+            continue;
+        };
+        // Parsed transaction converts back to the row it was read from.
+        let view = TransactionView::from(&transaction);
+        assert_eq!((view.client, view.tx), (client, tx));
+        assert_eq!(Transaction::try_from(view).unwrap(), transaction);
+    }
+});
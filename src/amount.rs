@@ -0,0 +1,210 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Amount of money. Arithmetic is checked, so balances never wrap or panic.
+///
+/// Serialized as a string, so JSON consumers do not lose precision.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(Decimal);
+
+/// Result of an arithmetic operation does not fit into an `Amount`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountError {
+    Overflow,
+}
+
+/// `Amount` literal, `amount!(1.5)`.
+#[macro_export]
+macro_rules! amount {
+    ($($value:tt)+) => {
+        $crate::Amount::new($crate::__dec!($($value)+))
+    };
+}
+
+impl Amount {
+    pub const ZERO: Amount = Amount(Decimal::ZERO);
+    /// Largest amount of a single deposit or withdrawal.
+    /// Leaves balances enough headroom to add up billions of them.
+    pub const MAX_TRANSACTION: Amount = Amount(dec!(1000000000000000));
+
+    pub const fn new(value: Decimal) -> Self {
+        Self(value)
+    }
+
+    pub const fn value(self) -> Decimal {
+        self.0
+    }
+
+    /// Amount that a deposit or withdrawal may carry: not negative and at most `MAX_TRANSACTION`.
+    pub fn is_valid_transaction(self) -> bool {
+        Self::ZERO <= self && self <= Self::MAX_TRANSACTION
+    }
+
+    pub fn checked_add(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0
+            .checked_add(other.0)
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0
+            .checked_sub(other.0)
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+
+    /// Sum clamped to the range of `Amount`, for totals that are only reported.
+    pub fn saturating_add(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_add(other.0))
+    }
+
+    /// Difference clamped to the range of `Amount`, for totals that are only reported.
+    pub fn saturating_sub(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_sub(other.0))
+    }
+}
+
+impl From<Decimal> for Amount {
+    fn from(value: Decimal) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Amount> for Decimal {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl FromStr for Amount {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Decimal::from_str(s).map(Amount)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountError::Overflow => f.write_str("amount is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    /// Accepts strings and numbers, including integers beyond 64 bits (CSV reads them as such).
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+struct AmountVisitor;
+
+impl Visitor<'_> for AmountVisitor {
+    type Value = Amount;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal amount")
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Amount, E> {
+        Ok(Amount(Decimal::from(value)))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Amount, E> {
+        Ok(Amount(Decimal::from(value)))
+    }
+
+    fn visit_i128<E: de::Error>(self, value: i128) -> Result<Amount, E> {
+        Decimal::try_from_i128_with_scale(value, 0)
+            .map(Amount)
+            .map_err(|_| E::custom(format!("amount {value} is out of range")))
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<Amount, E> {
+        match i128::try_from(value) {
+            Ok(value) => self.visit_i128(value),
+            Err(_) => Err(E::custom(format!("amount {value} is out of range"))),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Amount, E> {
+        // Shortest representation of the float, not its exact binary value.
+        Decimal::from_str(&value.to_string())
+            .map(Amount)
+            .map_err(|_| E::invalid_value(de::Unexpected::Float(value), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Amount, E> {
+        Decimal::from_str(value)
+            .or_else(|_| Decimal::from_scientific(value))
+            .map(Amount)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount::{Amount, AmountError};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let max = Amount::new(Decimal::MAX);
+        assert_eq!(amount!(1.5).checked_add(amount!(0.25)), Ok(amount!(1.75)));
+        assert_eq!(amount!(1.5).checked_sub(amount!(2)), Ok(amount!(-0.5)));
+        assert_eq!(max.checked_add(amount!(1)), Err(AmountError::Overflow));
+        assert_eq!(
+            Amount::new(Decimal::MIN).checked_sub(amount!(1)),
+            Err(AmountError::Overflow)
+        );
+        assert_eq!(max.saturating_add(max), max);
+
+        assert!(amount!(0).is_valid_transaction());
+        assert!(Amount::MAX_TRANSACTION.is_valid_transaction());
+        assert!(!amount!(-0.0001).is_valid_transaction());
+        assert!(!max.is_valid_transaction());
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(serde_json::to_string(&amount!(1.50)).unwrap(), "\"1.50\"");
+        assert_eq!(
+            serde_json::from_str::<Amount>("\"1.50\"").unwrap(),
+            amount!(1.5)
+        );
+        assert_eq!(serde_json::from_str::<Amount>("2").unwrap(), amount!(2));
+        assert!(serde_json::from_str::<Amount>("\"79228162514264337593543950336\"").is_err());
+        assert!(serde_json::from_str::<Amount>("79228162514264337593543950336").is_err());
+        assert!(serde_json::from_str::<Amount>("1e400").is_err());
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader("79228162514264337593543950335\n-18446744073709551616\n".as_bytes());
+        let amounts: Vec<Amount> = reader.deserialize().map(Result::unwrap).collect();
+        assert_eq!(
+            amounts,
+            vec![Amount::new(Decimal::MAX), amount!(-18446744073709551616)]
+        );
+    }
+}
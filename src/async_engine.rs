@@ -0,0 +1,99 @@
+use crate::audit::AuditEvent;
+use crate::client::{Client, ClientView};
+use crate::db::AsyncDatabase;
+use crate::engine::{EngineCore, CLIENT_LOCK_STRIPES};
+use crate::transactions::Transaction;
+use crate::types::{client_stripe, ClientID, EngineError, Outcome, Timestamp};
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Engine backed by an `AsyncDatabase`, built with `EngineBuilder::build_async`.
+///
+/// Applies the same rules as `Engine`, but awaits the database instead of blocking,
+/// and holds client locks that can be kept across awaits.
+pub struct AsyncEngine<D> {
+    core: EngineCore,
+    database: D,
+    client_locks: Vec<Mutex<()>>,
+}
+
+impl<D: AsyncDatabase> AsyncEngine<D> {
+    pub(crate) fn new(core: EngineCore, database: D) -> Self {
+        Self {
+            core,
+            database,
+            client_locks: (0..CLIENT_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+        }
+    }
+
+    pub async fn process_transaction(&self, tx: &Transaction) -> Result<Outcome, EngineError> {
+        if let Some(timestamp) = self.core.advance(tx) {
+            self.expire_disputes(timestamp).await?;
+        }
+        let client_id = tx.client();
+        let _guard = self.lock_client(client_id).await;
+        let stored = self.database.get_client(&client_id).await?;
+        let exists = stored.is_some();
+        let mut client = stored.unwrap_or_default();
+        let referenced = match self.core.needs_reference(tx) {
+            true => self.database.get_tx(&tx.uid()).await?,
+            false => None,
+        };
+        let applied = self.core.apply(tx, &mut client, exists, referenced)?;
+        if applied.changed {
+            if tx.is_recorded() && applied.outcome == Outcome::Applied {
+                self.database.write_tx(tx.clone()).await?;
+            }
+            self.database
+                .write_client(client_id, client.clone())
+                .await?;
+        }
+        let exists = exists || applied.outcome == Outcome::Applied;
+        self.core.log_event(
+            AuditEvent::new(tx, applied.outcome),
+            applied.amount,
+            exists.then_some(&client),
+        )?;
+        Ok(applied.outcome)
+    }
+
+    /// Apply timeout policy to all disputes with deadline not later than `now`.
+    async fn expire_disputes(&self, now: Timestamp) -> Result<(), EngineError> {
+        for (deadline, (client_id, tx_id)) in self.core.pop_expired(now)? {
+            let _guard = self.lock_client(client_id).await;
+            let mut client = self.get_client(&client_id).await?;
+            let referenced = self.database.get_tx(&(client_id, tx_id)).await?;
+            if let Some((event, amount)) =
+                self.core
+                    .expire(client_id, tx_id, deadline, &mut client, referenced)?
+            {
+                self.database
+                    .write_client(client_id, client.clone())
+                    .await?;
+                self.core.log_event(event, amount, Some(&client))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get client from db by ID, or create an empty client
+    pub async fn get_client(&self, id: &ClientID) -> Result<Client, EngineError> {
+        Ok(self.database.get_client(id).await?.unwrap_or_default())
+    }
+
+    /// Get a vector of clients in output-friendly form
+    pub async fn get_all_clients(&self) -> Result<Vec<ClientView>, EngineError> {
+        Ok(self
+            .database
+            .all_clients()
+            .await?
+            .iter()
+            .map(|(id, client)| client.get_view(*id))
+            .collect())
+    }
+
+    async fn lock_client(&self, id: ClientID) -> MutexGuard<'_, ()> {
+        self.client_locks[client_stripe(id, self.client_locks.len())]
+            .lock()
+            .await
+    }
+}
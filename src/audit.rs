@@ -0,0 +1,43 @@
+use crate::transactions::Transaction;
+use crate::types::{ClientID, Outcome, Timestamp, TxID};
+use serde::Serialize;
+
+/// Record of a single processed transaction, either from the input
+/// or synthesized by the engine itself (e.g. dispute timeouts).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    #[serde(rename = "type")]
+    pub type_str: &'static str,
+    pub client: ClientID,
+    pub tx: TxID,
+    pub timestamp: Option<Timestamp>,
+    pub result: Outcome,
+}
+
+impl AuditEvent {
+    pub fn new(tx: &Transaction, outcome: Outcome) -> Self {
+        Self {
+            type_str: tx.type_str(),
+            client: tx.client(),
+            tx: tx.id(),
+            timestamp: tx.timestamp(),
+            result: outcome,
+        }
+    }
+
+    /// Engine-generated event that was not present in the input.
+    pub fn synthetic(
+        type_str: &'static str,
+        client: ClientID,
+        tx: TxID,
+        timestamp: Timestamp,
+    ) -> Self {
+        Self {
+            type_str,
+            client,
+            tx,
+            timestamp: Some(timestamp),
+            result: Outcome::Applied,
+        }
+    }
+}
@@ -1,12 +1,16 @@
-use crate::transactions::{DepositTransaction, WithdrawalTransaction};
-use crate::types::{Amount, ClientError, ClientID, TxID};
+use crate::amount::AmountError;
+use crate::transactions::{DepositTransaction, Transaction, WithdrawalTransaction};
+use crate::types::{Amount, ClientError, ClientID, Outcome, Rejection, Timestamp, TxID};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::fmt::Debug;
 
 #[derive(Debug, Default, PartialEq, Clone)]
 pub enum ClientStatus {
     #[default]
     Live,
+    /// Withdrawals are rejected, everything else is processed as usual.
+    Restricted,
     Frozen,
 }
 
@@ -17,11 +21,14 @@ pub struct Client {
     pub status: ClientStatus,
     /// Set of all disputed transactions without resolution for this Client
     pub disputes: HashSet<TxID>,
+    /// Timestamp of the latest processed transaction, if it had one
+    pub last_timestamp: Option<Timestamp>,
 }
 
+/// Client balances for output, `C` is `String` for external client references.
 #[derive(Serialize, Deserialize, Debug, Hash, PartialEq, Eq)]
-pub struct ClientView {
-    pub client: ClientID,
+pub struct ClientView<C = ClientID> {
+    pub client: C,
     pub available: Amount,
     pub held: Amount,
     pub total: Amount,
@@ -33,39 +40,122 @@ impl Client {
         self.status == ClientStatus::Frozen
     }
 
-    pub fn process_deposit(&mut self, tx: &DepositTransaction) -> Result<(), ClientError> {
-        self.available += tx.amount;
-        Ok(())
+    pub fn process_deposit(&mut self, tx: &DepositTransaction) -> Result<Outcome, ClientError> {
+        Ok(self.update_balances(tx, self.available.checked_add(tx.amount), Ok(self.held)))
     }
 
-    pub fn process_withdrawal(&mut self, tx: &WithdrawalTransaction) -> Result<(), ClientError> {
-        if self.available < tx.amount {
+    pub fn is_restricted(&self) -> bool {
+        self.status == ClientStatus::Restricted
+    }
+
+    pub fn process_withdrawal(
+        &mut self,
+        tx: &WithdrawalTransaction,
+        allow_overdraft: bool,
+    ) -> Result<Outcome, ClientError> {
+        if self.is_restricted() {
+            log::warn!(target: "client", "Withdrawals are restricted for {tx:?}. {self:?}");
+            return Ok(Outcome::Rejected(Rejection::WithdrawalsRestricted));
+        }
+        if self.available < tx.amount && !allow_overdraft {
             log::warn!(target: "client", "Insufficient funds for withdrawal {tx:?}. {self:?}");
-            return Ok(());
+            return Ok(Outcome::Rejected(Rejection::InsufficientFunds));
         }
-        self.available -= tx.amount;
-        Ok(())
+        Ok(self.update_balances(tx, self.available.checked_sub(tx.amount), Ok(self.held)))
     }
 
-    pub fn process_dispute(&mut self, tx: &DepositTransaction) -> Result<(), ClientError> {
-        self.available -= tx.amount;
-        self.held += tx.amount;
-        self.disputes.insert(tx.tx);
-        Ok(())
+    /// Hold funds of a disputed deposit or withdrawal.
+    ///
+    /// Deposit moves its amount from available to held funds, the total does not change.
+    /// Withdrawal adds its amount to held funds as a provisional credit,
+    /// the total grows until the dispute is resolved (credit dropped)
+    /// or charged back (credit moved to available funds).
+    pub fn process_dispute(&mut self, tx: &Transaction) -> Result<Outcome, ClientError> {
+        debug_assert!(tx.is_recorded());
+        let outcome = match tx {
+            Transaction::Deposit(deposit) => self.update_balances(
+                tx,
+                self.available.checked_sub(deposit.amount),
+                self.held.checked_add(deposit.amount),
+            ),
+            // Withdrawal still stands, its amount is credited as held until the dispute is settled.
+            Transaction::Withdrawal(withdrawal) => self.update_balances(
+                tx,
+                Ok(self.available),
+                self.held.checked_add(withdrawal.amount),
+            ),
+            _ => Outcome::Applied,
+        };
+        if outcome == Outcome::Applied {
+            self.disputes.insert(tx.id());
+        }
+        Ok(outcome)
     }
 
-    pub fn process_chargeback(&mut self, tx: &DepositTransaction) -> Result<(), ClientError> {
-        self.held -= tx.amount;
-        self.disputes.remove(&tx.tx);
-        self.status = ClientStatus::Frozen;
-        Ok(())
+    /// Reverse the disputed deposit or withdrawal and freeze the account.
+    pub fn process_chargeback(&mut self, tx: &Transaction) -> Result<Outcome, ClientError> {
+        debug_assert!(tx.is_recorded());
+        let outcome = match tx {
+            Transaction::Deposit(deposit) => self.update_balances(
+                tx,
+                Ok(self.available),
+                self.held.checked_sub(deposit.amount),
+            ),
+            Transaction::Withdrawal(withdrawal) => self.update_balances(
+                tx,
+                self.available.checked_add(withdrawal.amount),
+                self.held.checked_sub(withdrawal.amount),
+            ),
+            _ => Outcome::Applied,
+        };
+        if outcome == Outcome::Applied {
+            self.disputes.remove(&tx.id());
+            self.status = ClientStatus::Frozen;
+        }
+        Ok(outcome)
     }
 
-    pub fn process_resolve(&mut self, tx: &DepositTransaction) -> Result<(), ClientError> {
-        self.held -= tx.amount;
-        self.available += tx.amount;
-        self.disputes.remove(&tx.tx);
-        Ok(())
+    /// Release funds held by the dispute, the transaction stands.
+    pub fn process_resolve(&mut self, tx: &Transaction) -> Result<Outcome, ClientError> {
+        debug_assert!(tx.is_recorded());
+        let outcome = match tx {
+            Transaction::Deposit(deposit) => self.update_balances(
+                tx,
+                self.available.checked_add(deposit.amount),
+                self.held.checked_sub(deposit.amount),
+            ),
+            Transaction::Withdrawal(withdrawal) => self.update_balances(
+                tx,
+                Ok(self.available),
+                self.held.checked_sub(withdrawal.amount),
+            ),
+            _ => Outcome::Applied,
+        };
+        if outcome == Outcome::Applied {
+            self.disputes.remove(&tx.id());
+        }
+        Ok(outcome)
+    }
+
+    /// Set new balances, unless any of them or their total does not fit into an `Amount`.
+    /// Client is left untouched if the update is rejected.
+    fn update_balances(
+        &mut self,
+        tx: &impl Debug,
+        available: Result<Amount, AmountError>,
+        held: Result<Amount, AmountError>,
+    ) -> Outcome {
+        match (available, held) {
+            (Ok(available), Ok(held)) if available.checked_add(held).is_ok() => {
+                self.available = available;
+                self.held = held;
+                Outcome::Applied
+            }
+            _ => {
+                log::warn!(target: "client", "Balance overflow for {tx:?}. {self:?}");
+                Outcome::Rejected(Rejection::BalanceOverflow)
+            }
+        }
     }
 
     pub fn get_view(&self, id: ClientID) -> ClientView {
@@ -73,7 +163,8 @@ impl Client {
             client: id,
             available: self.available,
             held: self.held,
-            total: self.available + self.held,
+            // Never saturates, balances are only updated if their total fits.
+            total: self.available.saturating_add(self.held),
             locked: self.is_frozen(),
         }
     }
@@ -0,0 +1,152 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Writer that compresses data on the fly.
+/// `finish` has to be called to write the end of the compressed stream.
+pub enum CompressedWriter<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl Compression {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    pub fn from_magic(header: &[u8]) -> Self {
+        if header.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wrap input into a decompressing reader, detecting compression by magic bytes.
+pub fn decompress<'a, R: BufRead + Send + 'a>(
+    mut input: R,
+) -> std::io::Result<Box<dyn Read + Send + 'a>> {
+    // A single read of a pipe may return fewer bytes than the longest magic.
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut input)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::from_magic(&magic);
+    let input = Cursor::new(magic).chain(input);
+    Ok(match compression {
+        Compression::None => Box::new(input),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(input)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(input)?),
+    })
+}
+
+/// Open input file, decompressing it if needed.
+///
+/// Compression is detected by extension, falling back to magic bytes.
+pub fn open_input(path: &Path) -> std::io::Result<Box<dyn Read + Send>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(match Compression::from_path(path) {
+        Compression::None => decompress(reader)?,
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+    })
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(output: W, compression: Compression) -> std::io::Result<Self> {
+        Ok(match compression {
+            Compression::None => CompressedWriter::None(output),
+            Compression::Gzip => {
+                CompressedWriter::Gzip(GzEncoder::new(output, flate2::Compression::default()))
+            }
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(output, 0)?),
+        })
+    }
+
+    /// Write the end of the compressed stream and flush the output.
+    pub fn finish(self) -> std::io::Result<W> {
+        let mut output = match self {
+            CompressedWriter::None(output) => output,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        output.flush()?;
+        Ok(output)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressedWriter::None(output) => output.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressedWriter::None(output) => output.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compression::{decompress, CompressedWriter, Compression};
+    use std::io::{BufReader, Read, Write};
+
+    const DATA: &str = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+
+    fn roundtrip(compression: Compression) {
+        let mut writer = CompressedWriter::new(vec![], compression).unwrap();
+        writer.write_all(DATA.as_bytes()).unwrap();
+        let compressed = writer.finish().unwrap();
+        assert_eq!(Compression::from_magic(&compressed), compression);
+
+        let mut output = String::new();
+        decompress(compressed.as_slice())
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, DATA);
+
+        // Input that arrives one byte at a time, like a slow pipe.
+        let mut output = String::new();
+        decompress(BufReader::with_capacity(1, compressed.as_slice()))
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, DATA);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        roundtrip(Compression::None);
+        roundtrip(Compression::Gzip);
+        roundtrip(Compression::Zstd);
+    }
+}
@@ -1,30 +1,69 @@
-use crate::client::{Client, ClientView};
+use crate::client::Client;
 use crate::transactions::Transaction;
 use crate::types::{ClientID, DatabaseError, TxUID};
 
 use std::collections::HashMap;
+use std::future::{ready, Future};
 use std::sync::RwLock;
 
+/// Transaction record that can be stored in a database.
+pub trait TransactionRecord: Clone + Send + Sync {
+    /// Key of the record, unique among stored transactions.
+    fn uid(&self) -> TxUID;
+}
+
 /// Database trait needed for Engine.
-pub trait Database: Send + Sync {
-    fn get_tx(&self, id: &TxUID) -> Result<Option<Transaction>, DatabaseError>;
-    fn write_tx(&self, tx: Transaction) -> Result<(), DatabaseError>;
-    fn get_client(&self, id: &ClientID) -> Result<Option<Client>, DatabaseError>;
-    fn write_client(&self, id: ClientID, client: Client) -> Result<(), DatabaseError>;
+///
+/// Generic over the stored account record `A` and transaction record `T`,
+/// the engine stores `Client` and `Transaction`.
+pub trait Database<A = Client, T = Transaction>: Send + Sync {
+    fn get_tx(&self, id: &TxUID) -> Result<Option<T>, DatabaseError>;
+    fn write_tx(&self, tx: T) -> Result<(), DatabaseError>;
+    fn transaction_count(&self) -> Result<usize, DatabaseError>;
+    /// Stored transactions of the client ordered by id.
+    fn client_transactions(&self, id: &ClientID) -> Result<Vec<T>, DatabaseError>;
+    fn get_client(&self, id: &ClientID) -> Result<Option<A>, DatabaseError>;
+    fn write_client(&self, id: ClientID, client: A) -> Result<(), DatabaseError>;
     // TODO: return iterator
-    fn all_clients(&self) -> Result<Vec<ClientView>, DatabaseError>;
+    fn all_clients(&self) -> Result<Vec<(ClientID, A)>, DatabaseError>;
+}
+
+/// Database trait needed for AsyncEngine.
+///
+/// Implementations may do I/O without blocking executor threads.
+pub trait AsyncDatabase<A = Client, T = Transaction>: Send + Sync {
+    fn get_tx(&self, id: &TxUID) -> impl Future<Output = Result<Option<T>, DatabaseError>> + Send;
+    fn write_tx(&self, tx: T) -> impl Future<Output = Result<(), DatabaseError>> + Send;
+    fn get_client(
+        &self,
+        id: &ClientID,
+    ) -> impl Future<Output = Result<Option<A>, DatabaseError>> + Send;
+    fn write_client(
+        &self,
+        id: ClientID,
+        client: A,
+    ) -> impl Future<Output = Result<(), DatabaseError>> + Send;
+    fn all_clients(&self)
+        -> impl Future<Output = Result<Vec<(ClientID, A)>, DatabaseError>> + Send;
 }
 
 /// Uses simple HashMaps to save clients and transactions.
 ///
 /// Uses locks be thread-safe.
-pub struct InMemoryDB {
-    clients: RwLock<HashMap<ClientID, Client>>,
-    transactions: RwLock<HashMap<TxUID, Transaction>>,
+pub struct InMemoryDB<A = Client, T = Transaction> {
+    clients: RwLock<HashMap<ClientID, A>>,
+    transactions: RwLock<HashMap<TxUID, T>>,
 }
 
 impl InMemoryDB {
+    /// Database of the records stored by the engine.
     pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<A, T> Default for InMemoryDB<A, T> {
+    fn default() -> Self {
         Self {
             clients: RwLock::new(HashMap::new()),
             transactions: RwLock::new(HashMap::new()),
@@ -32,16 +71,19 @@ impl InMemoryDB {
     }
 }
 
-impl Database for InMemoryDB {
-    fn get_tx(&self, id: &TxUID) -> Result<Option<Transaction>, DatabaseError> {
+impl<A, T> Database<A, T> for InMemoryDB<A, T>
+where
+    A: Clone + Send + Sync,
+    T: TransactionRecord,
+{
+    fn get_tx(&self, id: &TxUID) -> Result<Option<T>, DatabaseError> {
         match self.transactions.read() {
             Ok(db) => Ok(db.get(id).cloned()),
             Err(_) => Err(DatabaseError::PoisonLock),
         }
     }
 
-    fn write_tx(&self, tx: Transaction) -> Result<(), DatabaseError> {
-        debug_assert!(tx.is_recorded());
+    fn write_tx(&self, tx: T) -> Result<(), DatabaseError> {
         match self.transactions.write() {
             Ok(mut db) => db.insert(tx.uid(), tx),
             Err(_) => return Err(DatabaseError::PoisonLock),
@@ -49,14 +91,37 @@ impl Database for InMemoryDB {
         Ok(())
     }
 
-    fn get_client(&self, id: &ClientID) -> Result<Option<Client>, DatabaseError> {
+    fn transaction_count(&self) -> Result<usize, DatabaseError> {
+        match self.transactions.read() {
+            Ok(db) => Ok(db.len()),
+            Err(_) => Err(DatabaseError::PoisonLock),
+        }
+    }
+
+    fn client_transactions(&self, id: &ClientID) -> Result<Vec<T>, DatabaseError> {
+        // TODO: index transactions by client
+        match self.transactions.read() {
+            Ok(db) => {
+                let mut transactions: Vec<T> = db
+                    .iter()
+                    .filter(|((client, _), _)| client == id)
+                    .map(|(_, tx)| tx.clone())
+                    .collect();
+                transactions.sort_by_key(|tx| tx.uid());
+                Ok(transactions)
+            }
+            Err(_) => Err(DatabaseError::PoisonLock),
+        }
+    }
+
+    fn get_client(&self, id: &ClientID) -> Result<Option<A>, DatabaseError> {
         match self.clients.read() {
             Ok(db) => Ok(db.get(id).cloned()),
             Err(_) => Err(DatabaseError::PoisonLock),
         }
     }
 
-    fn write_client(&self, id: ClientID, client: Client) -> Result<(), DatabaseError> {
+    fn write_client(&self, id: ClientID, client: A) -> Result<(), DatabaseError> {
         match self.clients.write() {
             Ok(mut db) => db.insert(id, client),
             Err(_) => return Err(DatabaseError::PoisonLock),
@@ -64,22 +129,59 @@ impl Database for InMemoryDB {
         Ok(())
     }
 
-    fn all_clients(&self) -> Result<Vec<ClientView>, DatabaseError> {
+    fn all_clients(&self) -> Result<Vec<(ClientID, A)>, DatabaseError> {
         match self.clients.read() {
             Ok(db) => Ok(db
                 .iter()
-                .map(|(id, client)| client.get_view(*id))
-                .collect::<Vec<ClientView>>()),
+                .map(|(id, client)| (*id, client.clone()))
+                .collect()),
             Err(_) => Err(DatabaseError::PoisonLock),
         }
     }
 }
 
+/// Every operation completes immediately, as it never waits for I/O.
+impl<A, T> AsyncDatabase<A, T> for InMemoryDB<A, T>
+where
+    A: Clone + Send + Sync,
+    T: TransactionRecord,
+{
+    fn get_tx(&self, id: &TxUID) -> impl Future<Output = Result<Option<T>, DatabaseError>> + Send {
+        ready(Database::get_tx(self, id))
+    }
+
+    fn write_tx(&self, tx: T) -> impl Future<Output = Result<(), DatabaseError>> + Send {
+        ready(Database::write_tx(self, tx))
+    }
+
+    fn get_client(
+        &self,
+        id: &ClientID,
+    ) -> impl Future<Output = Result<Option<A>, DatabaseError>> + Send {
+        ready(Database::get_client(self, id))
+    }
+
+    fn write_client(
+        &self,
+        id: ClientID,
+        client: A,
+    ) -> impl Future<Output = Result<(), DatabaseError>> + Send {
+        ready(Database::write_client(self, id, client))
+    }
+
+    fn all_clients(
+        &self,
+    ) -> impl Future<Output = Result<Vec<(ClientID, A)>, DatabaseError>> + Send {
+        ready(Database::all_clients(self))
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::db::{Database, InMemoryDB};
+    use crate::amount;
+    use crate::db::{Database, InMemoryDB, TransactionRecord};
     use crate::transactions::{DepositTransaction, Transaction, WithdrawalTransaction};
-    use rust_decimal_macros::dec;
+    use crate::types::{Amount, ClientID, TxID, TxUID};
 
     #[test]
     fn test_write_get() {
@@ -87,12 +189,14 @@ mod test {
         let tx1 = Transaction::Deposit(DepositTransaction {
             client: 10,
             tx: 1,
-            amount: dec!(100.0),
+            amount: amount!(100.0),
+            timestamp: None,
         });
         let tx2 = Transaction::Deposit(DepositTransaction {
             client: 12,
             tx: 5,
-            amount: dec!(90.0),
+            amount: amount!(90.0),
+            timestamp: None,
         });
         assert_eq!(db.get_tx(&(10, 1)).unwrap(), None);
         assert_eq!(db.get_tx(&(12, 5)).unwrap(), None);
@@ -108,11 +212,45 @@ mod test {
         let tx2_new = Transaction::Withdrawal(WithdrawalTransaction {
             client: 12,
             tx: 5,
-            amount: dec!(20.0),
+            amount: amount!(20.0),
+            timestamp: None,
         });
         db.write_tx(tx2_new.clone()).unwrap();
         assert_eq!(db.get_tx(&(10, 1)).unwrap(), Some(tx1.clone()));
         assert_eq!(db.get_tx(&(12, 5)).unwrap(), Some(tx2_new.clone()));
         assert_eq!(db.get_tx(&(10, 7)).unwrap(), None);
+        assert_eq!(db.transaction_count().unwrap(), 2);
+        assert_eq!(db.client_transactions(&12).unwrap(), vec![tx2_new]);
+        assert_eq!(db.client_transactions(&11).unwrap(), vec![]);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Entry {
+        client: ClientID,
+        tx: TxID,
+        amount: Amount,
+    }
+
+    impl TransactionRecord for Entry {
+        fn uid(&self) -> TxUID {
+            (self.client, self.tx)
+        }
+    }
+
+    #[test]
+    fn test_custom_records() {
+        let db = InMemoryDB::<Amount, Entry>::default();
+        let entry = |client, tx, amount| Entry { client, tx, amount };
+        db.write_tx(entry(1, 2, amount!(1.0))).unwrap();
+        db.write_tx(entry(1, 1, amount!(2.0))).unwrap();
+        db.write_tx(entry(2, 1, amount!(3.0))).unwrap();
+        assert_eq!(
+            db.client_transactions(&1).unwrap(),
+            vec![entry(1, 1, amount!(2.0)), entry(1, 2, amount!(1.0))]
+        );
+        db.write_client(1, amount!(3.0)).unwrap();
+        db.write_client(1, amount!(4.0)).unwrap();
+        assert_eq!(db.get_client(&1).unwrap(), Some(amount!(4.0)));
+        assert_eq!(db.all_clients().unwrap(), vec![(1, amount!(4.0))]);
     }
 }
@@ -1,114 +1,701 @@
-use crate::client::{Client, ClientView};
-use crate::db::{Database, InMemoryDB};
-use crate::transactions::{DepositTransaction, Transaction};
-use crate::types::{ClientID, EngineError};
+use crate::async_engine::AsyncEngine;
+use crate::audit::AuditEvent;
+use crate::client::{Client, ClientStatus, ClientView};
+use crate::db::{AsyncDatabase, Database, InMemoryDB};
+use crate::expiry::{DisputeSchedule, DisputeTimeout, DisputeTimeoutPolicy};
+use crate::fraud::{FlaggedClientView, FraudAction, FraudConfig, FraudDetector, FraudEvent};
+use crate::history::{AsOf, BalanceHistory};
+use crate::policy::{DisputePolicy, EnginePolicy, FrozenPolicy, IdUniqueness};
+use crate::statement::{build_statement, StatementEntry};
+use crate::transactions::{DisputeTransaction, Transaction};
+use crate::types::{
+    client_stripe, Amount, ClientID, EngineError, Outcome, Rejection, Timestamp, TxID, TxUID,
+};
 use log::warn;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Storage-independent part of the engine: configuration and bookkeeping kept in memory.
+///
+/// Callers load the client and the referenced transaction, let the core apply the
+/// transaction to them and store the result, so the same rules serve both
+/// the blocking `Engine` and the `AsyncEngine`.
+pub(crate) struct EngineCore {
+    fraud: FraudDetector,
+    /// Maximum time between a deposit and its dispute.
+    dispute_window: Option<Timestamp>,
+    dispute_timeout: Option<DisputeTimeout>,
+    policy: EnginePolicy,
+    hooks: Vec<EventHook>,
+    schedule: Mutex<DisputeSchedule>,
+    /// Every processed transaction, only kept if enabled.
+    audit_log: Option<Mutex<Vec<AuditEvent>>>,
+    /// Balances after every change, only kept if enabled.
+    history: Option<BalanceHistory>,
+    /// Number of transactions passed to the engine so far.
+    processed: AtomicU64,
+    /// Latest timestamp seen so far.
+    clock: AtomicU64,
+}
+
+/// Callback run after every processed transaction and every engine-generated event
+/// with the balances of the client right after it, None if the client does not exist.
+///
+/// Runs on the processing thread while the client is locked, so it has to be quick.
+pub type EventHook = Box<dyn Fn(&AuditEvent, Option<&ClientView>) + Send + Sync>;
 
 pub struct Engine {
+    core: EngineCore,
     database: Arc<dyn Database>,
+    /// Transactions of a client are applied under the lock of its stripe,
+    /// so concurrent callers never interleave updates of the same client.
+    client_locks: Vec<Mutex<()>>,
 }
 
-impl Engine {
+/// Configuration of an `Engine` or an `AsyncEngine`.
+pub struct EngineBuilder {
+    core: EngineCore,
+    database: Arc<dyn Database>,
+}
+
+/// Number of locks shared by all clients.
+pub const CLIENT_LOCK_STRIPES: usize = 64;
+
+/// Transaction applied to a loaded client.
+pub(crate) struct Applied {
+    pub outcome: Outcome,
+    /// Client has to be stored, its last timestamp may have changed even if rejected.
+    /// The transaction itself is only stored if it was applied.
+    pub changed: bool,
+    /// Amount of the transaction, or of the transaction it refers to.
+    pub amount: Option<Amount>,
+}
+
+impl EngineBuilder {
+    /// Engine without optional features over an `InMemoryDB`.
     pub fn new() -> Self {
         Self {
+            core: EngineCore {
+                fraud: FraudDetector::new(FraudConfig::default()),
+                dispute_window: None,
+                dispute_timeout: None,
+                policy: EnginePolicy::default(),
+                hooks: vec![],
+                schedule: Mutex::new(DisputeSchedule::default()),
+                audit_log: None,
+                history: None,
+                processed: AtomicU64::new(0),
+                clock: AtomicU64::new(0),
+            },
             database: Arc::new(InMemoryDB::new()),
         }
     }
 
-    pub fn process_transaction(&self, tx: &Transaction) -> Result<(), EngineError> {
+    /// Store clients and transactions in `database`.
+    pub fn with_database(mut self, database: Arc<dyn Database>) -> Self {
+        self.database = database;
+        self
+    }
+
+    /// Apply business rules of `policy`.
+    pub fn with_policy(mut self, policy: EnginePolicy) -> Self {
+        self.core.policy = policy;
+        self
+    }
+
+    /// Run `hook` after every event, hooks run in the order they were added.
+    pub fn with_hook(
+        mut self,
+        hook: impl Fn(&AuditEvent, Option<&ClientView>) + Send + Sync + 'static,
+    ) -> Self {
+        self.core.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Enable fraud detection with the given rules.
+    pub fn with_fraud_config(mut self, config: FraudConfig) -> Self {
+        self.core.fraud = FraudDetector::new(config);
+        self
+    }
+
+    /// Reject disputes that come later than `window` seconds after the deposit.
+    pub fn with_dispute_window(mut self, window: Timestamp) -> Self {
+        self.core.dispute_window = Some(window);
+        self
+    }
+
+    /// Apply `timeout.policy` to timestamped disputes left open for `timeout.period` seconds.
+    pub fn with_dispute_timeout(mut self, timeout: DisputeTimeout) -> Self {
+        self.core.dispute_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep a record of every processed transaction, including synthetic ones.
+    pub fn with_audit_log(mut self) -> Self {
+        self.core.audit_log = Some(Mutex::new(vec![]));
+        self
+    }
+
+    /// Keep balances of every client after every change to answer point-in-time queries.
+    pub fn with_balance_history(mut self) -> Self {
+        self.core.history = Some(BalanceHistory::default());
+        self
+    }
+
+    pub fn build(self) -> Engine {
+        Engine {
+            core: self.core,
+            database: self.database,
+            client_locks: (0..CLIENT_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+        }
+    }
+
+    /// Build an async engine backed by `database` instead of the configured one.
+    pub fn build_async<D: AsyncDatabase>(self, database: D) -> AsyncEngine<D> {
+        AsyncEngine::new(self.core, database)
+    }
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    /// Engine without optional features over an `InMemoryDB`.
+    pub fn new() -> Self {
+        EngineBuilder::new().build()
+    }
+
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+
+    pub fn process_transaction(&self, tx: &Transaction) -> Result<Outcome, EngineError> {
+        if let Some(timestamp) = self.core.advance(tx) {
+            self.expire_disputes(timestamp)?;
+        }
         let client_id = tx.client();
-        let mut client = self.get_client(&client_id)?;
+        let _guard = self.lock_client(client_id)?;
+        let stored = self.database.get_client(&client_id)?;
+        let exists = stored.is_some();
+        let mut client = stored.unwrap_or_default();
+        let referenced = match self.core.needs_reference(tx) {
+            true => self.database.get_tx(&tx.uid())?,
+            false => None,
+        };
+        let applied = self.core.apply(tx, &mut client, exists, referenced)?;
+        if applied.changed {
+            // Only record applied Deposit or Withdrawal transactions,
+            // rejected ones cannot be disputed.
+            if tx.is_recorded() && applied.outcome == Outcome::Applied {
+                self.database.write_tx(tx.clone())?;
+            }
+            // Update Client entry in the db.
+            self.database.write_client(client_id, client.clone())?;
+        }
+        // Client only exists if it was stored before or the transaction created it.
+        let exists = exists || applied.outcome == Outcome::Applied;
+        self.core.log_event(
+            AuditEvent::new(tx, applied.outcome),
+            applied.amount,
+            exists.then_some(&client),
+        )?;
+        Ok(applied.outcome)
+    }
+
+    /// Apply timeout policy to all disputes with deadline not later than `now`.
+    fn expire_disputes(&self, now: Timestamp) -> Result<(), EngineError> {
+        for (deadline, (client_id, tx_id)) in self.core.pop_expired(now)? {
+            let _guard = self.lock_client(client_id)?;
+            let mut client = self.get_client(&client_id)?;
+            let referenced = self.database.get_tx(&(client_id, tx_id))?;
+            if let Some((event, amount)) =
+                self.core
+                    .expire(client_id, tx_id, deadline, &mut client, referenced)?
+            {
+                self.database.write_client(client_id, client.clone())?;
+                self.core.log_event(event, amount, Some(&client))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get client from db by ID, or create an empty client
+    pub fn get_client(&self, id: &ClientID) -> Result<Client, EngineError> {
+        Ok(match self.database.get_client(id)? {
+            Some(client) => client.clone(),
+            None => Client::default(),
+        })
+    }
+
+    /// Get client in output-friendly form, None if the client does not exist
+    pub fn get_client_view(&self, id: &ClientID) -> Result<Option<ClientView>, EngineError> {
+        Ok(self
+            .database
+            .get_client(id)?
+            .map(|client| client.get_view(*id)))
+    }
+
+    /// Get stored deposits and withdrawals of the client ordered by id
+    pub fn get_client_transactions(&self, id: &ClientID) -> Result<Vec<Transaction>, EngineError> {
+        Ok(self.database.client_transactions(id)?)
+    }
+
+    /// Get a stored deposit or withdrawal
+    pub fn get_transaction(&self, id: &TxUID) -> Result<Option<Transaction>, EngineError> {
+        Ok(self.database.get_tx(id)?)
+    }
+
+    /// Get the number of stored deposits and withdrawals
+    pub fn count_transactions(&self) -> Result<usize, EngineError> {
+        Ok(self.database.transaction_count()?)
+    }
+
+    /// Get a vector of clients in output-friendly form
+    pub fn get_all_clients(&self) -> Result<Vec<ClientView>, EngineError> {
+        Ok(self
+            .database
+            .all_clients()?
+            .iter()
+            .map(|(id, client)| client.get_view(*id))
+            .collect())
+    }
+
+    /// Get a vector of clients flagged by fraud detection in output-friendly form
+    pub fn get_flagged_clients(&self) -> Result<Vec<FlaggedClientView>, EngineError> {
+        self.core.fraud.flagged_clients()
+    }
+
+    /// Get client balances after the first N transactions or at the given time.
+    /// Returns None if the client did not exist at that point
+    /// or if balance history is not enabled.
+    pub fn has_balance_history(&self) -> bool {
+        self.core.history.is_some()
+    }
+
+    pub fn get_client_at(
+        &self,
+        id: &ClientID,
+        as_of: AsOf,
+    ) -> Result<Option<ClientView>, EngineError> {
+        match &self.core.history {
+            Some(history) => history.get(id, as_of),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a vector of clients as of the given point in output-friendly form,
+    /// empty if balance history is not enabled
+    pub fn get_all_clients_at(&self, as_of: AsOf) -> Result<Vec<ClientView>, EngineError> {
+        match &self.core.history {
+            Some(history) => history.get_all(as_of),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Get chronological statement of the client,
+    /// empty if balance history is not enabled
+    pub fn get_statement(&self, id: &ClientID) -> Result<Vec<StatementEntry>, EngineError> {
+        match &self.core.history {
+            Some(history) => Ok(build_statement(&history.records(id)?)),
+            None => Ok(vec![]),
+        }
+    }
 
-        // Ignore transaction if the client is frozen.
-        if client.is_frozen() {
+    /// Get statements of all clients ordered by client id,
+    /// empty if balance history is not enabled
+    pub fn get_all_statements(&self) -> Result<Vec<StatementEntry>, EngineError> {
+        let Some(history) = &self.core.history else {
+            return Ok(vec![]);
+        };
+        let mut statements = vec![];
+        for id in history.client_ids()? {
+            statements.extend(self.get_statement(&id)?);
+        }
+        Ok(statements)
+    }
+
+    /// Get a copy of the audit log, empty if it is not enabled
+    pub fn get_audit_log(&self) -> Result<Vec<AuditEvent>, EngineError> {
+        self.core.get_audit_log()
+    }
+
+    /// Get business rules applied by the engine
+    pub fn policy(&self) -> &EnginePolicy {
+        &self.core.policy
+    }
+
+    fn lock_client(&self, id: ClientID) -> Result<MutexGuard<'_, ()>, EngineError> {
+        self.client_locks[client_stripe(id, self.client_locks.len())]
+            .lock()
+            .map_err(|_| EngineError::PoisonLock)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EngineCore {
+    /// Count the transaction and move input time forward.
+    /// Returns the time to expire disputes at, if the transaction has a timestamp.
+    pub fn advance(&self, tx: &Transaction) -> Option<Timestamp> {
+        self.processed.fetch_add(1, Ordering::SeqCst);
+        // Input time only moves forward with timestamped transactions.
+        let timestamp = tx.timestamp()?;
+        self.clock.fetch_max(timestamp, Ordering::SeqCst);
+        Some(timestamp)
+    }
+
+    /// Whether the stored transaction with the same id has to be loaded for `apply`.
+    pub fn needs_reference(&self, tx: &Transaction) -> bool {
+        !tx.is_recorded() || self.policy.unique_ids == IdUniqueness::PerClient
+    }
+
+    /// Remove and return all disputes with deadline not later than `now`.
+    pub fn pop_expired(&self, now: Timestamp) -> Result<Vec<(Timestamp, TxUID)>, EngineError> {
+        if self.dispute_timeout.is_none() {
+            return Ok(vec![]);
+        }
+        Ok(self
+            .schedule
+            .lock()
+            .map_err(|_| EngineError::PoisonLock)?
+            .pop_expired(now))
+    }
+
+    /// Apply timeout policy to an expired dispute of the loaded client.
+    /// Returns the synthetic event with the disputed amount
+    /// if the client was changed and has to be stored.
+    pub fn expire(
+        &self,
+        client_id: ClientID,
+        tx_id: TxID,
+        deadline: Timestamp,
+        client: &mut Client,
+        referenced: Option<Transaction>,
+    ) -> Result<Option<(AuditEvent, Option<Amount>)>, EngineError> {
+        let Some(timeout) = self.dispute_timeout else {
+            return Ok(None);
+        };
+        // Frozen clients ignore everything, including timeouts.
+        if client.is_frozen() || !client.disputes.contains(&tx_id) {
+            return Ok(None);
+        }
+        let Some(disputed) = referenced.filter(Transaction::is_recorded) else {
+            return Ok(None);
+        };
+        warn!(target: "engine", "Dispute timed out, applying {:?} to {disputed:?}.", timeout.policy);
+        let (outcome, type_str) = match timeout.policy {
+            DisputeTimeoutPolicy::Resolve => (client.process_resolve(&disputed)?, "auto_resolve"),
+            DisputeTimeoutPolicy::Chargeback => {
+                (client.process_chargeback(&disputed)?, "auto_chargeback")
+            }
+        };
+        if outcome != Outcome::Applied {
+            return Ok(None);
+        }
+        if timeout.policy == DisputeTimeoutPolicy::Chargeback {
+            self.record_fraud_event(client_id, client, FraudEvent::Chargeback)?;
+        }
+        Ok(Some((
+            AuditEvent::synthetic(type_str, client_id, tx_id, deadline),
+            disputed.amount(),
+        )))
+    }
+
+    /// Apply transaction to the loaded client.
+    /// `client` is a new one if the client is not stored yet (`exists` is false),
+    /// it is only stored if the transaction is applied.
+    /// `referenced` is the stored transaction with the same id,
+    /// only loaded if `needs_reference` asks for it.
+    pub fn apply(
+        &self,
+        tx: &Transaction,
+        client: &mut Client,
+        exists: bool,
+        referenced: Option<Transaction>,
+    ) -> Result<Applied, EngineError> {
+        let client_id = tx.client();
+        let amount = tx
+            .amount()
+            .or_else(|| referenced.as_ref().and_then(Transaction::amount));
+
+        // Ignore transaction if the client is frozen, unless the policy lets it through.
+        let allowed_when_frozen = matches!(tx, Transaction::Deposit(_))
+            && self.policy.frozen == FrozenPolicy::AllowDeposits;
+        if client.is_frozen() && !allowed_when_frozen {
             warn!(target: "engine", "Unable to process transaction {tx:?}. Client's account is frozen {client:?}.");
-            return Ok(());
+            return Ok(Applied::unchanged(Rejection::FrozenAccount, amount));
         }
-        match tx {
+        // Timestamps are optional, but have to be monotonic for every client.
+        if let (Some(timestamp), Some(last_timestamp)) = (tx.timestamp(), client.last_timestamp) {
+            if timestamp < last_timestamp {
+                warn!(target: "engine", "Transaction {tx:?} is older than the previous one for the client {client:?}.");
+                return Ok(Applied::unchanged(Rejection::OutOfOrder, amount));
+            }
+        }
+        if !exists && !tx.is_recorded() && self.policy.validation.reject_unknown_clients {
+            warn!(target: "engine", "Transaction {tx:?} refers to an unknown client.");
+            return Ok(Applied::unchanged(Rejection::UnknownClient, amount));
+        }
+        if let Some(rejection) = self.validate(tx, referenced.as_ref()) {
+            return Ok(Applied::unchanged(rejection, amount));
+        }
+        // Replacing a transaction under dispute would leave its funds held for good.
+        if tx.is_recorded() && client.disputes.contains(&tx.id()) {
+            warn!(target: "engine", "Transaction {tx:?} reuses the id of a disputed one.");
+            return Ok(Applied::unchanged(Rejection::DuplicateTransaction, amount));
+        }
+        let mut fraud_event = None;
+        let outcome = match tx {
             Transaction::Deposit(deposit) => {
-                client.process_deposit(deposit)?;
+                let outcome = client.process_deposit(deposit)?;
+                if outcome == Outcome::Applied {
+                    fraud_event = Some(FraudEvent::Deposit);
+                }
+                outcome
             }
             Transaction::Withdrawal(withdrawal) => {
-                client.process_withdrawal(withdrawal)?;
+                client.process_withdrawal(withdrawal, self.policy.validation.allow_overdraft)?
             }
-            Transaction::Dispute(_) => {
-                if let Some(referenced_deposit) = self.get_referenced_tx(tx)? {
-                    client.process_dispute(&referenced_deposit)?;
-                }
+            // Repeated dispute would hold the funds of the transaction once more.
+            Transaction::Dispute(dispute) if client.disputes.contains(&dispute.tx) => {
+                warn!(target: "engine", "Transaction is disputed already {dispute:?}.");
+                Outcome::Rejected(Rejection::AlreadyDisputed)
             }
+            Transaction::Dispute(dispute) => match self.disputed_transaction(tx, referenced) {
+                Err(rejection) => Outcome::Rejected(rejection),
+                Ok(disputed) if self.is_dispute_expired(dispute, &disputed) => {
+                    warn!(target: "engine", "Dispute window has passed for {disputed:?}. {dispute:?}");
+                    Outcome::Rejected(Rejection::DisputeExpired)
+                }
+                Ok(disputed) => match client.process_dispute(&disputed)? {
+                    Outcome::Applied => {
+                        fraud_event = Some(FraudEvent::Dispute);
+                        if let (Some(timeout), Some(timestamp)) =
+                            (self.dispute_timeout, dispute.timestamp)
+                        {
+                            self.schedule
+                                .lock()
+                                .map_err(|_| EngineError::PoisonLock)?
+                                .schedule(tx.uid(), timestamp.saturating_add(timeout.period));
+                        }
+                        Outcome::Applied
+                    }
+                    rejected => rejected,
+                },
+            },
             Transaction::Resolve(resolve) => {
                 if !client.disputes.contains(&resolve.tx) {
                     warn!(target: "engine", "Cannot resolve transaction that is not disputed {resolve:?}.");
-                } else if let Some(referenced_deposit) = self.get_referenced_tx(tx)? {
-                    client.process_resolve(&referenced_deposit)?;
+                    Outcome::Rejected(Rejection::NotDisputed)
+                } else {
+                    match self.disputed_transaction(tx, referenced) {
+                        Err(rejection) => Outcome::Rejected(rejection),
+                        Ok(disputed) => {
+                            let outcome = client.process_resolve(&disputed)?;
+                            if outcome == Outcome::Applied {
+                                self.cancel_dispute_timeout(tx)?;
+                            }
+                            outcome
+                        }
+                    }
                 }
             }
             Transaction::Chargeback(chargeback) => {
                 if !client.disputes.contains(&chargeback.tx) {
                     warn!(target: "engine", "Cannot charge back transaction that is not disputed {chargeback:?}.");
-                } else if let Some(referenced_deposit) = self.get_referenced_tx(tx)? {
-                    client.process_chargeback(&referenced_deposit)?;
+                    Outcome::Rejected(Rejection::NotDisputed)
+                } else {
+                    match self.disputed_transaction(tx, referenced) {
+                        Err(rejection) => Outcome::Rejected(rejection),
+                        Ok(disputed) => {
+                            let outcome = client.process_chargeback(&disputed)?;
+                            if outcome == Outcome::Applied {
+                                self.cancel_dispute_timeout(tx)?;
+                                fraud_event = Some(FraudEvent::Chargeback);
+                            }
+                            outcome
+                        }
+                    }
                 }
             }
+        };
+        if let Some(event) = fraud_event {
+            self.record_fraud_event(client_id, client, event)?;
         }
-        // Only record Deposit or Withdrawal transactions.
-        if tx.is_recorded() {
-            self.database.write_tx(tx.clone())?;
+        if let Some(timestamp) = tx.timestamp() {
+            client.last_timestamp = Some(timestamp);
+        }
+        Ok(Applied {
+            outcome,
+            // Rejected transactions never create a client.
+            changed: exists || outcome == Outcome::Applied,
+            amount,
+        })
+    }
+
+    /// Save event to the audit log and the balance history, if they are enabled.
+    /// `amount` is the amount of the transaction, or of the transaction it refers to,
+    /// `client` is the state of the client right after the event, None if it does not exist.
+    pub fn log_event(
+        &self,
+        event: AuditEvent,
+        amount: Option<Amount>,
+        client: Option<&Client>,
+    ) -> Result<(), EngineError> {
+        if let (Some(history), Some(client)) = (&self.history, client) {
+            history.record(
+                self.processed.load(Ordering::SeqCst),
+                self.clock.load(Ordering::SeqCst),
+                event.clone(),
+                amount,
+                client,
+            )?;
+        }
+        if !self.hooks.is_empty() {
+            let view = client.map(|client| client.get_view(event.client));
+            for hook in &self.hooks {
+                hook(&event, view.as_ref());
+            }
+        }
+        if let Some(log) = &self.audit_log {
+            log.lock().map_err(|_| EngineError::PoisonLock)?.push(event);
         }
-        // Update Client entry in the db.
-        self.database.write_client(client_id, client)?;
         Ok(())
     }
 
-    /// Get client from db by ID, or create an empty client
-    pub fn get_client(&self, id: &ClientID) -> Result<Client, EngineError> {
-        Ok(match self.database.get_client(id)? {
-            Some(client) => client.clone(),
-            None => Client::default(),
+    /// Get a copy of the audit log, empty if it is not enabled
+    pub fn get_audit_log(&self) -> Result<Vec<AuditEvent>, EngineError> {
+        Ok(match &self.audit_log {
+            Some(log) => log.lock().map_err(|_| EngineError::PoisonLock)?.clone(),
+            None => vec![],
         })
     }
 
-    /// Get a vector of clients in output-friendly form
-    pub fn get_all_clients(&self) -> Result<Vec<ClientView>, EngineError> {
-        Ok(self.database.all_clients()?)
+    fn record_fraud_event(
+        &self,
+        client_id: ClientID,
+        client: &mut Client,
+        event: FraudEvent,
+    ) -> Result<(), EngineError> {
+        match self.fraud.record(client_id, event)? {
+            Some(FraudAction::Freeze) => client.status = ClientStatus::Frozen,
+            Some(FraudAction::RestrictWithdrawals) if !client.is_frozen() => {
+                client.status = ClientStatus::Restricted
+            }
+            _ => {}
+        }
+        Ok(())
     }
 
-    /// Get original transaction by id from meta-transaction.
-    /// Does not panic if transaction is not in the db (returns None instead).
-    /// If the transaction is not a Deposit, also returns None.
-    fn get_referenced_tx(
+    fn cancel_dispute_timeout(&self, tx: &Transaction) -> Result<(), EngineError> {
+        if self.dispute_timeout.is_some() {
+            self.schedule
+                .lock()
+                .map_err(|_| EngineError::PoisonLock)?
+                .cancel(&tx.uid());
+        }
+        Ok(())
+    }
+
+    /// Check a deposit or withdrawal against the range of amounts, the validation rules
+    /// and id uniqueness.
+    fn validate(&self, tx: &Transaction, referenced: Option<&Transaction>) -> Option<Rejection> {
+        let amount = tx.amount()?;
+        if !amount.is_valid_transaction() {
+            warn!(target: "engine", "Amount of {tx:?} is negative or above {}.", Amount::MAX_TRANSACTION);
+            return Some(Rejection::InvalidAmount);
+        }
+        if let Some(max_amount) = self.policy.validation.max_amount {
+            if amount > max_amount {
+                warn!(target: "engine", "Amount of {tx:?} is above the maximum of {max_amount}.");
+                return Some(Rejection::InvalidAmount);
+            }
+        }
+        if self.policy.unique_ids == IdUniqueness::PerClient && referenced.is_some() {
+            warn!(target: "engine", "Transaction {tx:?} reuses the id of {referenced:?}.");
+            return Some(Rejection::DuplicateTransaction);
+        }
+        None
+    }
+
+    /// Get original transaction of a meta-transaction.
+    /// Returns rejection if the transaction is not in the db
+    /// or the dispute policy does not allow to dispute it.
+    fn disputed_transaction(
         &self,
         tx: &Transaction,
-    ) -> Result<Option<DepositTransaction>, EngineError> {
-        let tx = self.database.get_tx(&tx.uid())?;
-        match tx {
+        referenced: Option<Transaction>,
+    ) -> Result<Transaction, Rejection> {
+        match referenced {
             None => {
                 warn!("Disputed transaction is absent from DB. {:?}", tx);
-                Ok(None)
+                Err(Rejection::MissingTransaction)
+            }
+            Some(deposit @ Transaction::Deposit(_)) => Ok(deposit),
+            Some(withdrawal @ Transaction::Withdrawal(_))
+                if self.policy.disputes == DisputePolicy::DepositsAndWithdrawals =>
+            {
+                Ok(withdrawal)
             }
-            Some(Transaction::Deposit(deposit)) => Ok(Some(deposit)),
             Some(ref other_tx) => {
                 warn!(
                     "Disputed transaction is not a deposit. {:?} {:?}",
                     tx, other_tx
                 );
-                Ok(None)
+                Err(Rejection::NotADeposit)
+            }
+        }
+    }
+
+    /// Dispute is expired only if both transactions have timestamps
+    /// and the dispute came later than the configured window allows.
+    fn is_dispute_expired(&self, dispute: &DisputeTransaction, disputed: &Transaction) -> bool {
+        match (self.dispute_window, dispute.timestamp, disputed.timestamp()) {
+            (Some(window), Some(disputed_at), Some(recorded_at)) => {
+                disputed_at.saturating_sub(recorded_at) > window
             }
+            _ => false,
+        }
+    }
+}
+
+impl Applied {
+    fn unchanged(rejection: Rejection, amount: Option<Amount>) -> Self {
+        Self {
+            outcome: Outcome::Rejected(rejection),
+            changed: false,
+            amount,
         }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::amount;
+    use crate::audit::AuditEvent;
     use crate::client::{Client, ClientStatus};
+    use crate::db::{Database, InMemoryDB};
     use crate::engine::Engine;
+    use crate::expiry::{DisputeTimeout, DisputeTimeoutPolicy};
+    use crate::fraud::{FraudAction, FraudConfig};
+    use crate::history::AsOf;
+    use crate::policy::{DisputePolicy, EnginePolicy, FrozenPolicy, IdUniqueness, ValidationRules};
     use crate::transactions::{
         ChargebackTransaction, DepositTransaction, DisputeTransaction, ResolveTransaction,
         Transaction, WithdrawalTransaction,
     };
-    use rust_decimal_macros::dec;
+    use crate::types::{Amount, ClientID, Outcome, Rejection, TxID};
+    use proptest::prelude::*;
+    use rust_decimal::Decimal;
     use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_deposit() {
@@ -116,16 +703,18 @@ mod test {
         let tx1 = Transaction::Deposit(DepositTransaction {
             client: 10,
             tx: 1,
-            amount: dec!(100.0),
+            amount: amount!(100.0),
+            timestamp: None,
         });
         engine.process_transaction(&tx1).unwrap();
         assert_eq!(
             engine.get_client(&10).unwrap(),
             Client {
-                available: dec!(100.0),
-                held: dec!(0.0),
+                available: amount!(100.0),
+                held: amount!(0.0),
                 status: ClientStatus::Live,
                 disputes: HashSet::new(),
+                last_timestamp: None,
             }
         );
     }
@@ -136,37 +725,42 @@ mod test {
         let tx1 = Transaction::Deposit(DepositTransaction {
             client: 10,
             tx: 1,
-            amount: dec!(100.0),
+            amount: amount!(100.0),
+            timestamp: None,
         });
         engine.process_transaction(&tx1).unwrap();
         let tx2 = Transaction::Withdrawal(WithdrawalTransaction {
             client: 10,
             tx: 2,
-            amount: dec!(90.0),
+            amount: amount!(90.0),
+            timestamp: None,
         });
         engine.process_transaction(&tx2).unwrap();
         assert_eq!(
             engine.get_client(&10).unwrap(),
             Client {
-                available: dec!(10.0),
-                held: dec!(0.0),
+                available: amount!(10.0),
+                held: amount!(0.0),
                 status: ClientStatus::Live,
                 disputes: HashSet::new(),
+                last_timestamp: None,
             }
         );
         let tx3 = Transaction::Withdrawal(WithdrawalTransaction {
             client: 10,
             tx: 3,
-            amount: dec!(20.0),
+            amount: amount!(20.0),
+            timestamp: None,
         });
         engine.process_transaction(&tx3).unwrap();
         assert_eq!(
             engine.get_client(&10).unwrap(),
             Client {
-                available: dec!(10.0),
-                held: dec!(0.0),
+                available: amount!(10.0),
+                held: amount!(0.0),
                 status: ClientStatus::Live,
                 disputes: HashSet::new(),
+                last_timestamp: None,
             }
         );
     }
@@ -177,42 +771,119 @@ mod test {
         let tx1 = Transaction::Deposit(DepositTransaction {
             client: 10,
             tx: 1,
-            amount: dec!(100.0),
+            amount: amount!(100.0),
+            timestamp: None,
         });
         engine.process_transaction(&tx1).unwrap();
-        let tx2 = Transaction::Dispute(DisputeTransaction { client: 10, tx: 1 });
+        let tx2 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: None,
+        });
         engine.process_transaction(&tx2).unwrap();
         assert_eq!(
             engine.get_client(&10).unwrap(),
             Client {
-                available: dec!(0.0),
-                held: dec!(100.0),
+                available: amount!(0.0),
+                held: amount!(100.0),
                 status: ClientStatus::Live,
                 disputes: HashSet::from([1]),
+                last_timestamp: None,
             }
         );
     }
 
+    #[test]
+    fn test_reuse_of_disputed_id() {
+        let engine = Engine::new();
+        for (tx, outcome) in [
+            (Transaction::deposit(10, 1, amount!(100)), Outcome::Applied),
+            (Transaction::dispute(10, 1), Outcome::Applied),
+            (
+                Transaction::deposit(10, 1, amount!(5)),
+                Outcome::Rejected(Rejection::DuplicateTransaction),
+            ),
+            (Transaction::resolve(10, 1), Outcome::Applied),
+            // Once the dispute is closed the id can be reused again.
+            (Transaction::deposit(10, 1, amount!(5)), Outcome::Applied),
+        ] {
+            assert_eq!(engine.process_transaction(&tx).unwrap(), outcome);
+        }
+        let view = engine.get_client_view(&10).unwrap().unwrap();
+        assert_eq!(
+            (view.available, view.held, view.total),
+            (amount!(105), amount!(0), amount!(105))
+        );
+    }
+
+    #[test]
+    fn test_repeated_dispute() {
+        let engine = Engine::new();
+        let dispute = Transaction::dispute(10, 1);
+        for tx in [
+            Transaction::deposit(10, 1, amount!(100.0)),
+            Transaction::deposit(10, 2, amount!(50.0)),
+            dispute.clone(),
+        ] {
+            assert_eq!(engine.process_transaction(&tx).unwrap(), Outcome::Applied);
+        }
+        assert_eq!(
+            engine.process_transaction(&dispute).unwrap(),
+            Outcome::Rejected(Rejection::AlreadyDisputed)
+        );
+        let client = engine.get_client(&10).unwrap();
+        assert_eq!(
+            (client.available, client.held),
+            (amount!(50.0), amount!(100.0))
+        );
+
+        // Closed dispute can be opened again.
+        let resolve = Transaction::resolve(10, 1);
+        assert_eq!(
+            engine.process_transaction(&resolve).unwrap(),
+            Outcome::Applied
+        );
+        assert_eq!(
+            engine.process_transaction(&dispute).unwrap(),
+            Outcome::Applied
+        );
+        let client = engine.get_client(&10).unwrap();
+        assert_eq!(
+            (client.available, client.held),
+            (amount!(50.0), amount!(100.0))
+        );
+    }
+
     #[test]
     fn test_chargeback() {
         let engine = Engine::new();
         let tx1 = Transaction::Deposit(DepositTransaction {
             client: 10,
             tx: 1,
-            amount: dec!(100.0),
+            amount: amount!(100.0),
+            timestamp: None,
         });
         engine.process_transaction(&tx1).unwrap();
-        let tx2 = Transaction::Dispute(DisputeTransaction { client: 10, tx: 1 });
+        let tx2 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: None,
+        });
         engine.process_transaction(&tx2).unwrap();
-        let tx3 = Transaction::Chargeback(ChargebackTransaction { client: 10, tx: 1 });
+        let tx3 = Transaction::Chargeback(ChargebackTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: None,
+        });
         engine.process_transaction(&tx3).unwrap();
         assert_eq!(
             engine.get_client(&10).unwrap(),
             Client {
-                available: dec!(0.0),
-                held: dec!(0.0),
+                available: amount!(0.0),
+                held: amount!(0.0),
                 status: ClientStatus::Frozen,
                 disputes: HashSet::new(),
+                last_timestamp: None,
             }
         );
     }
@@ -223,21 +894,651 @@ mod test {
         let tx1 = Transaction::Deposit(DepositTransaction {
             client: 10,
             tx: 1,
-            amount: dec!(100.0),
+            amount: amount!(100.0),
+            timestamp: None,
         });
         engine.process_transaction(&tx1).unwrap();
-        let tx2 = Transaction::Dispute(DisputeTransaction { client: 10, tx: 1 });
+        let tx2 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: None,
+        });
         engine.process_transaction(&tx2).unwrap();
-        let tx3 = Transaction::Resolve(ResolveTransaction { client: 10, tx: 1 });
+        let tx3 = Transaction::Resolve(ResolveTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: None,
+        });
         engine.process_transaction(&tx3).unwrap();
         assert_eq!(
             engine.get_client(&10).unwrap(),
             Client {
-                available: dec!(100.0),
-                held: dec!(0.0),
+                available: amount!(100.0),
+                held: amount!(0.0),
                 status: ClientStatus::Live,
                 disputes: HashSet::new(),
+                last_timestamp: None,
             }
         );
     }
+
+    #[test]
+    fn test_fraud_detection() {
+        let engine = Engine::builder()
+            .with_fraud_config(FraudConfig {
+                rules: vec![
+                    "disputes:1:restrict".parse().unwrap(),
+                    "disputes:2:freeze".parse().unwrap(),
+                ],
+            })
+            .build();
+        for tx in 1..=3 {
+            let deposit = Transaction::Deposit(DepositTransaction {
+                client: 10,
+                tx,
+                amount: amount!(100.0),
+                timestamp: None,
+            });
+            engine.process_transaction(&deposit).unwrap();
+        }
+        let tx4 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: None,
+        });
+        engine.process_transaction(&tx4).unwrap();
+        assert_eq!(
+            engine.get_client(&10).unwrap().status,
+            ClientStatus::Restricted
+        );
+        let tx5 = Transaction::Withdrawal(WithdrawalTransaction {
+            client: 10,
+            tx: 5,
+            amount: amount!(50.0),
+            timestamp: None,
+        });
+        engine.process_transaction(&tx5).unwrap();
+        assert_eq!(engine.get_client(&10).unwrap().available, amount!(200.0));
+        let tx6 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 2,
+            timestamp: None,
+        });
+        engine.process_transaction(&tx6).unwrap();
+        assert_eq!(
+            engine.get_client(&10).unwrap(),
+            Client {
+                available: amount!(100.0),
+                held: amount!(200.0),
+                status: ClientStatus::Frozen,
+                disputes: HashSet::from([1, 2]),
+                last_timestamp: None,
+            }
+        );
+        let flagged = engine.get_flagged_clients().unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].client, 10);
+        assert_eq!(flagged[0].action, FraudAction::Freeze);
+    }
+
+    #[test]
+    fn test_dispute_timeout() {
+        let engine = Engine::builder()
+            .with_dispute_timeout(DisputeTimeout {
+                period: 100,
+                policy: DisputeTimeoutPolicy::Chargeback,
+            })
+            .with_audit_log()
+            .build();
+        for tx in 1..=2 {
+            let deposit = Transaction::Deposit(DepositTransaction {
+                client: 10,
+                tx,
+                amount: amount!(100.0),
+                timestamp: Some(tx),
+            });
+            engine.process_transaction(&deposit).unwrap();
+        }
+        let tx3 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: Some(10),
+        });
+        engine.process_transaction(&tx3).unwrap();
+        let tx4 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 2,
+            timestamp: Some(20),
+        });
+        engine.process_transaction(&tx4).unwrap();
+        let tx5 = Transaction::Resolve(ResolveTransaction {
+            client: 10,
+            tx: 2,
+            timestamp: Some(30),
+        });
+        engine.process_transaction(&tx5).unwrap();
+        // Time advances through a different client.
+        let tx6 = Transaction::Deposit(DepositTransaction {
+            client: 11,
+            tx: 1,
+            amount: amount!(1.0),
+            timestamp: Some(150),
+        });
+        engine.process_transaction(&tx6).unwrap();
+        assert_eq!(
+            engine.get_client(&10).unwrap(),
+            Client {
+                available: amount!(100.0),
+                held: amount!(0.0),
+                status: ClientStatus::Frozen,
+                disputes: HashSet::new(),
+                last_timestamp: Some(30),
+            }
+        );
+        let audit_log = engine.get_audit_log().unwrap();
+        assert_eq!(audit_log.len(), 7);
+        assert_eq!(
+            audit_log[5],
+            AuditEvent::synthetic("auto_chargeback", 10, 1, 110)
+        );
+        assert_eq!(audit_log[6], AuditEvent::new(&tx6, Outcome::Applied));
+    }
+
+    #[test]
+    fn test_balance_history() {
+        let engine = Engine::builder().with_balance_history().build();
+        let txs = [
+            Transaction::Deposit(DepositTransaction {
+                client: 10,
+                tx: 1,
+                amount: amount!(100.0),
+                timestamp: Some(1000),
+            }),
+            Transaction::Deposit(DepositTransaction {
+                client: 11,
+                tx: 1,
+                amount: amount!(5.0),
+                timestamp: Some(1010),
+            }),
+            Transaction::Withdrawal(WithdrawalTransaction {
+                client: 10,
+                tx: 2,
+                amount: amount!(30.0),
+                timestamp: Some(1020),
+            }),
+            Transaction::Dispute(DisputeTransaction {
+                client: 10,
+                tx: 1,
+                timestamp: Some(1030),
+            }),
+        ];
+        for tx in &txs {
+            engine.process_transaction(tx).unwrap();
+        }
+        assert_eq!(engine.get_client_at(&10, AsOf::Index(0)).unwrap(), None);
+        let after_withdrawal = engine.get_client_at(&10, AsOf::Index(3)).unwrap().unwrap();
+        assert_eq!(after_withdrawal.available, amount!(70.0));
+        assert_eq!(after_withdrawal.held, amount!(0.0));
+        let at_time = engine
+            .get_client_at(&10, AsOf::Time(1025))
+            .unwrap()
+            .unwrap();
+        assert_eq!(at_time, after_withdrawal);
+        let latest = engine
+            .get_client_at(&10, AsOf::Time(5000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.available, amount!(-30.0));
+        assert_eq!(latest.held, amount!(100.0));
+        assert_eq!(
+            engine.get_all_clients_at(AsOf::Time(1005)).unwrap().len(),
+            1
+        );
+        assert_eq!(engine.get_all_clients_at(AsOf::Index(2)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_statement() {
+        let engine = Engine::builder().with_balance_history().build();
+        let tx1 = Transaction::Deposit(DepositTransaction {
+            client: 10,
+            tx: 1,
+            amount: amount!(100.0),
+            timestamp: None,
+        });
+        engine.process_transaction(&tx1).unwrap();
+        let tx2 = Transaction::Withdrawal(WithdrawalTransaction {
+            client: 10,
+            tx: 2,
+            amount: amount!(200.0),
+            timestamp: None,
+        });
+        engine.process_transaction(&tx2).unwrap();
+        let tx3 = Transaction::Dispute(DisputeTransaction {
+            client: 10,
+            tx: 1,
+            timestamp: None,
+        });
+        engine.process_transaction(&tx3).unwrap();
+
+        let statement = engine.get_statement(&10).unwrap();
+        assert_eq!(statement.len(), 3);
+        assert_eq!(statement[1].type_str, "withdrawal");
+        assert_eq!(statement[1].amount, Some(amount!(200.0)));
+        assert_eq!(statement[1].available_change, amount!(0.0));
+        assert_eq!(statement[1].note, Some("insufficient_funds".to_string()));
+        assert_eq!(statement[2].type_str, "dispute");
+        assert_eq!(statement[2].amount, Some(amount!(100.0)));
+        assert_eq!(statement[2].available_change, amount!(-100.0));
+        assert_eq!(statement[2].held_change, amount!(100.0));
+        assert_eq!(statement[2].total, amount!(100.0));
+        assert_eq!(statement[2].note, None);
+        assert_eq!(engine.get_all_statements().unwrap(), statement);
+
+        // Amounts are the ones seen when the transaction was processed.
+        let engine = Engine::builder().with_balance_history().build();
+        let txs = [
+            Transaction::deposit(10, 1, amount!(100.0)),
+            Transaction::deposit(10, 1, amount!(5.0)),
+            Transaction::dispute(10, 1),
+            Transaction::chargeback(10, 1),
+            Transaction::deposit(10, 2, amount!(7.0)),
+        ];
+        for tx in &txs {
+            engine.process_transaction(tx).unwrap();
+        }
+        let amounts: Vec<_> = engine
+            .get_statement(&10)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.amount)
+            .collect();
+        assert_eq!(
+            amounts,
+            [
+                amount!(100.0),
+                amount!(5.0),
+                amount!(5.0),
+                amount!(5.0),
+                amount!(7.0)
+            ]
+            .map(Some)
+        );
+    }
+
+    #[test]
+    fn test_policies() {
+        let engine = Engine::builder()
+            .with_policy(EnginePolicy {
+                frozen: FrozenPolicy::AllowDeposits,
+                disputes: DisputePolicy::DepositsAndWithdrawals,
+                validation: ValidationRules {
+                    max_amount: Some(amount!(1000.0)),
+                    allow_overdraft: true,
+                    reject_unknown_clients: false,
+                },
+                unique_ids: IdUniqueness::PerClient,
+            })
+            .build();
+        let txs = [
+            (
+                Transaction::deposit(10, 1, amount!(100.0)),
+                Outcome::Applied,
+            ),
+            (
+                Transaction::deposit(10, 2, amount!(1000.5)),
+                Outcome::Rejected(Rejection::InvalidAmount),
+            ),
+            (
+                Transaction::deposit(10, 1, amount!(5.0)),
+                Outcome::Rejected(Rejection::DuplicateTransaction),
+            ),
+            (
+                Transaction::withdrawal(10, 3, amount!(150.0)),
+                Outcome::Applied,
+            ),
+            (Transaction::dispute(10, 3), Outcome::Applied),
+            (Transaction::resolve(10, 3), Outcome::Applied),
+            (Transaction::dispute(10, 3), Outcome::Applied),
+            (Transaction::chargeback(10, 3), Outcome::Applied),
+            (Transaction::deposit(10, 4, amount!(10.0)), Outcome::Applied),
+            (
+                Transaction::withdrawal(10, 5, amount!(10.0)),
+                Outcome::Rejected(Rejection::FrozenAccount),
+            ),
+        ];
+        for (tx, outcome) in txs {
+            assert_eq!(engine.process_transaction(&tx).unwrap(), outcome, "{tx:?}");
+        }
+        let client = engine.get_client(&10).unwrap();
+        assert_eq!(client.available, amount!(110.0));
+        assert_eq!(client.held, amount!(0.0));
+        assert!(client.is_frozen());
+
+        let engine = Engine::new();
+        engine
+            .process_transaction(&Transaction::withdrawal(10, 1, amount!(0.0)))
+            .unwrap();
+        assert_eq!(
+            engine
+                .process_transaction(&Transaction::dispute(10, 1))
+                .unwrap(),
+            Outcome::Rejected(Rejection::NotADeposit)
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let database = Arc::new(InMemoryDB::new());
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = events.clone();
+        let engine = Engine::builder()
+            .with_database(database.clone())
+            .with_hook(move |event, client| {
+                recorded.lock().unwrap().push((
+                    event.type_str,
+                    event.result,
+                    client.map(|client| client.available),
+                ));
+            })
+            .build();
+        engine
+            .process_transaction(&Transaction::deposit(10, 1, amount!(100.0)))
+            .unwrap();
+        engine
+            .process_transaction(&Transaction::withdrawal(10, 2, amount!(200.0)))
+            .unwrap();
+        assert_eq!(
+            database.get_client(&10).unwrap().unwrap().available,
+            amount!(100.0)
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ("deposit", Outcome::Applied, Some(amount!(100.0))),
+                (
+                    "withdrawal",
+                    Outcome::Rejected(Rejection::InsufficientFunds),
+                    Some(amount!(100.0))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_clients() {
+        let engine = Engine::builder().with_balance_history().build();
+        engine
+            .process_transaction(&Transaction::deposit(10, 1, amount!(100.0)))
+            .unwrap();
+        let clients = engine.get_all_clients().unwrap();
+        let rejected = [
+            Transaction::dispute(999, 1),
+            Transaction::resolve(999, 1),
+            Transaction::chargeback(999, 1),
+            Transaction::withdrawal(998, 2, amount!(5.0)).at(1000),
+            Transaction::dispute(10, 3),
+        ];
+        for tx in &rejected {
+            assert!(engine.process_transaction(tx).unwrap() != Outcome::Applied);
+        }
+        assert_eq!(engine.get_all_clients().unwrap(), clients);
+        assert_eq!(engine.get_client_view(&999).unwrap(), None);
+        assert_eq!(engine.get_all_clients_at(AsOf::Index(6)).unwrap(), clients);
+        assert_eq!(engine.count_transactions().unwrap(), 1);
+
+        let engine = Engine::builder()
+            .with_policy(EnginePolicy {
+                validation: ValidationRules {
+                    reject_unknown_clients: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .build();
+        assert_eq!(
+            engine
+                .process_transaction(&Transaction::dispute(999, 1))
+                .unwrap(),
+            Outcome::Rejected(Rejection::UnknownClient)
+        );
+        engine
+            .process_transaction(&Transaction::deposit(10, 1, amount!(100.0)))
+            .unwrap();
+        assert_eq!(
+            engine
+                .process_transaction(&Transaction::dispute(10, 2))
+                .unwrap(),
+            Outcome::Rejected(Rejection::MissingTransaction)
+        );
+        assert_eq!(engine.get_all_clients().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_withdrawal_disputes() {
+        let engine = Engine::builder()
+            .with_policy(EnginePolicy {
+                disputes: DisputePolicy::DepositsAndWithdrawals,
+                ..Default::default()
+            })
+            .build();
+        let balances = |engine: &Engine| {
+            let view = engine.get_client_view(&10).unwrap().unwrap();
+            (view.available, view.held, view.total)
+        };
+        let txs = [
+            (
+                Transaction::deposit(10, 1, amount!(100.0)),
+                Outcome::Applied,
+                (amount!(100.0), amount!(0.0), amount!(100.0)),
+            ),
+            (
+                Transaction::withdrawal(10, 2, amount!(30.0)),
+                Outcome::Applied,
+                (amount!(70.0), amount!(0.0), amount!(70.0)),
+            ),
+            (
+                Transaction::withdrawal(10, 3, amount!(500.0)),
+                Outcome::Rejected(Rejection::InsufficientFunds),
+                (amount!(70.0), amount!(0.0), amount!(70.0)),
+            ),
+            // Money that never left the account cannot be claimed back.
+            (
+                Transaction::dispute(10, 3),
+                Outcome::Rejected(Rejection::MissingTransaction),
+                (amount!(70.0), amount!(0.0), amount!(70.0)),
+            ),
+            // Withdrawn amount is credited as held while the dispute is open.
+            (
+                Transaction::dispute(10, 2),
+                Outcome::Applied,
+                (amount!(70.0), amount!(30.0), amount!(100.0)),
+            ),
+            (
+                Transaction::resolve(10, 2),
+                Outcome::Applied,
+                (amount!(70.0), amount!(0.0), amount!(70.0)),
+            ),
+            (
+                Transaction::dispute(10, 2),
+                Outcome::Applied,
+                (amount!(70.0), amount!(30.0), amount!(100.0)),
+            ),
+            // Chargeback reverses the withdrawal, the total is the deposited amount again.
+            (
+                Transaction::chargeback(10, 2),
+                Outcome::Applied,
+                (amount!(100.0), amount!(0.0), amount!(100.0)),
+            ),
+        ];
+        for (tx, outcome, expected) in txs {
+            assert_eq!(engine.process_transaction(&tx).unwrap(), outcome, "{tx:?}");
+            assert_eq!(balances(&engine), expected, "{tx:?}");
+        }
+        assert!(engine.get_client(&10).unwrap().is_frozen());
+    }
+
+    #[test]
+    fn test_rejected_transactions_not_stored() {
+        let engine = Engine::builder()
+            .with_policy(EnginePolicy {
+                disputes: DisputePolicy::DepositsAndWithdrawals,
+                ..Default::default()
+            })
+            .build();
+        let txs = [
+            (Transaction::deposit(10, 1, amount!(10.0)), Outcome::Applied),
+            (
+                Transaction::withdrawal(10, 2, amount!(50.0)),
+                Outcome::Rejected(Rejection::InsufficientFunds),
+            ),
+            (
+                Transaction::dispute(10, 2),
+                Outcome::Rejected(Rejection::MissingTransaction),
+            ),
+            (
+                Transaction::chargeback(10, 2),
+                Outcome::Rejected(Rejection::NotDisputed),
+            ),
+        ];
+        for (tx, outcome) in txs {
+            assert_eq!(engine.process_transaction(&tx).unwrap(), outcome, "{tx:?}");
+        }
+        assert_eq!(engine.get_transaction(&(10, 2)).unwrap(), None);
+        let view = engine.get_client_view(&10).unwrap().unwrap();
+        assert_eq!(
+            (view.available, view.held, view.total),
+            (amount!(10.0), amount!(0.0), amount!(10.0))
+        );
+    }
+
+    #[test]
+    fn test_amount_range() {
+        let engine = Engine::new();
+        let txs = [
+            (
+                Transaction::deposit(10, 1, amount!(-1.0)),
+                Outcome::Rejected(Rejection::InvalidAmount),
+            ),
+            (
+                Transaction::deposit(10, 2, Amount::new(Decimal::MAX)),
+                Outcome::Rejected(Rejection::InvalidAmount),
+            ),
+            (
+                Transaction::deposit(10, 3, Amount::MAX_TRANSACTION),
+                Outcome::Applied,
+            ),
+            (
+                Transaction::withdrawal(10, 4, amount!(-5.0)),
+                Outcome::Rejected(Rejection::InvalidAmount),
+            ),
+        ];
+        for (tx, outcome) in &txs {
+            assert_eq!(engine.process_transaction(tx).unwrap(), *outcome);
+        }
+        assert_eq!(
+            engine.get_client(&10).unwrap().available,
+            Amount::MAX_TRANSACTION
+        );
+
+        // Balances close to the limits of `Amount` are left untouched.
+        let database = Arc::new(InMemoryDB::new());
+        let rich = Client {
+            available: Amount::new(Decimal::MAX).checked_sub(amount!(1)).unwrap(),
+            ..Default::default()
+        };
+        let indebted = Client {
+            available: Amount::new(Decimal::MIN).checked_add(amount!(1)).unwrap(),
+            ..Default::default()
+        };
+        database.write_client(1, rich.clone()).unwrap();
+        database.write_client(2, indebted.clone()).unwrap();
+        let engine = Engine::builder()
+            .with_database(database)
+            .with_policy(EnginePolicy {
+                validation: ValidationRules {
+                    allow_overdraft: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .build();
+        assert_eq!(
+            engine
+                .process_transaction(&Transaction::deposit(1, 1, amount!(5.0)))
+                .unwrap(),
+            Outcome::Rejected(Rejection::BalanceOverflow)
+        );
+        assert_eq!(
+            engine
+                .process_transaction(&Transaction::withdrawal(2, 2, amount!(5.0)))
+                .unwrap(),
+            Outcome::Rejected(Rejection::BalanceOverflow)
+        );
+        assert_eq!(engine.get_client(&1).unwrap(), rich);
+        assert_eq!(engine.get_client(&2).unwrap(), indebted);
+    }
+
+    /// Any `Amount`, including the limits of its range.
+    fn any_amount() -> impl Strategy<Value = Amount> {
+        prop_oneof![
+            Just(Amount::new(Decimal::MAX)),
+            Just(Amount::new(Decimal::MIN)),
+            Just(Amount::MAX_TRANSACTION),
+            Just(Amount::ZERO),
+            (any::<[u32; 3]>(), any::<bool>(), 0..=28u32).prop_map(
+                |([lo, mid, hi], negative, scale)| {
+                    Amount::new(Decimal::from_parts(lo, mid, hi, negative, scale))
+                }
+            ),
+        ]
+    }
+
+    /// Transactions of a few clients with overlapping ids, so that they refer to each other.
+    fn any_transaction() -> impl Strategy<Value = Transaction> {
+        (0..5u8, 0..3u8, 0..8 as TxID, any_amount()).prop_map(|(kind, client, tx, amount)| {
+            let client = ClientID::from(client);
+            match kind {
+                0 => Transaction::deposit(client, tx, amount),
+                1 => Transaction::withdrawal(client, tx, amount),
+                2 => Transaction::dispute(client, tx),
+                3 => Transaction::resolve(client, tx),
+                _ => Transaction::chargeback(client, tx),
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_arbitrary_amounts_never_panic(
+            txs in prop::collection::vec(any_transaction(), 1..64),
+            permissive in any::<bool>(),
+        ) {
+            let policy = match permissive {
+                false => EnginePolicy::default(),
+                true => EnginePolicy {
+                    frozen: FrozenPolicy::AllowDeposits,
+                    disputes: DisputePolicy::DepositsAndWithdrawals,
+                    validation: ValidationRules {
+                        allow_overdraft: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            };
+            let engine = Engine::builder().with_policy(policy).with_balance_history().build();
+            for tx in &txs {
+                let outcome = engine.process_transaction(tx).unwrap();
+                if tx.amount().is_some_and(|amount| !amount.is_valid_transaction()) {
+                    prop_assert_ne!(outcome, Outcome::Applied);
+                }
+            }
+            for view in engine.get_all_clients().unwrap() {
+                prop_assert_eq!(view.available.checked_add(view.held), Ok(view.total));
+            }
+            for client in 0..3u8 {
+                engine.get_statement(&ClientID::from(client)).unwrap();
+            }
+        }
+    }
 }
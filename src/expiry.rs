@@ -0,0 +1,87 @@
+use crate::types::{ClientID, Timestamp, TxID, TxUID};
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+/// What happens to a dispute that was not resolved or charged back in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeTimeoutPolicy {
+    Resolve,
+    Chargeback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisputeTimeout {
+    /// Seconds after the dispute when the policy is applied.
+    pub period: Timestamp,
+    pub policy: DisputeTimeoutPolicy,
+}
+
+/// Deadlines of open timestamped disputes, ordered by time.
+#[derive(Debug, Default)]
+pub struct DisputeSchedule {
+    deadlines: BTreeSet<(Timestamp, ClientID, TxID)>,
+    pending: HashMap<TxUID, Timestamp>,
+}
+
+impl DisputeSchedule {
+    /// Set deadline for the dispute, replacing the previous one if it exists.
+    pub fn schedule(&mut self, uid: TxUID, deadline: Timestamp) {
+        self.cancel(&uid);
+        self.pending.insert(uid, deadline);
+        self.deadlines.insert((deadline, uid.0, uid.1));
+    }
+
+    /// Forget the deadline, e.g. when the dispute got resolved.
+    pub fn cancel(&mut self, uid: &TxUID) {
+        if let Some(deadline) = self.pending.remove(uid) {
+            self.deadlines.remove(&(deadline, uid.0, uid.1));
+        }
+    }
+
+    /// Remove and return all disputes with deadline not later than `now`.
+    pub fn pop_expired(&mut self, now: Timestamp) -> Vec<(Timestamp, TxUID)> {
+        let mut expired = vec![];
+        while let Some(&(deadline, client, tx)) = self.deadlines.first() {
+            if deadline > now {
+                break;
+            }
+            self.deadlines.pop_first();
+            self.pending.remove(&(client, tx));
+            expired.push((deadline, (client, tx)));
+        }
+        expired
+    }
+}
+
+impl FromStr for DisputeTimeoutPolicy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "resolve" => Ok(DisputeTimeoutPolicy::Resolve),
+            "chargeback" => Ok(DisputeTimeoutPolicy::Chargeback),
+            _ => Err("unexpected dispute timeout policy"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::expiry::DisputeSchedule;
+
+    #[test]
+    fn test_schedule() {
+        let mut schedule = DisputeSchedule::default();
+        schedule.schedule((1, 1), 100);
+        schedule.schedule((1, 2), 50);
+        schedule.schedule((2, 1), 70);
+        schedule.cancel(&(2, 1));
+        assert_eq!(schedule.pop_expired(49), vec![]);
+        assert_eq!(schedule.pop_expired(80), vec![(50, (1, 2))]);
+        // Disputing again moves the deadline.
+        schedule.schedule((1, 1), 200);
+        assert_eq!(schedule.pop_expired(150), vec![]);
+        assert_eq!(schedule.pop_expired(200), vec![(200, (1, 1))]);
+        assert_eq!(schedule.pop_expired(1000), vec![]);
+    }
+}
@@ -1,47 +1,481 @@
+use crate::async_engine::AsyncEngine;
+use crate::audit::AuditEvent;
 use crate::client::ClientView;
+use crate::db::AsyncDatabase;
 use crate::engine::Engine;
-use crate::transactions::{Transaction, TransactionView};
-use csv::{ReaderBuilder, WriterBuilder};
+use crate::fraud::FlaggedClientView;
+use crate::input::{read_csv, read_json_lines, InputError};
+use crate::output::{write_csv, write_json_record, write_records, OutputFormat};
+use crate::reorder::ReorderBuffer;
+use crate::statement::StatementEntry;
+use crate::stats::{RunStats, TransactionStats};
+use crate::transactions::{Transaction, TransactionError, TransactionView};
+use crate::types::{client_stripe, EngineError, Outcome, Timestamp};
+use crate::validate::{ValidationIssue, Validator};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 
-pub fn process_csv_transactions<R: std::io::Read>(input: R) -> Vec<ClientView> {
-    let engine = Engine::new();
+#[derive(Debug, Default, Clone)]
+pub struct FlowConfig {
+    /// Sort transactions by timestamp, allowing them to be late by this many seconds.
+    pub reorder_tolerance: Option<Timestamp>,
+    /// Stop processing at the first rejected transaction.
+    pub strict: bool,
+    /// Number of worker threads, transactions are routed to them by client.
+    /// Transactions are processed on the reading thread if it is at most 1
+    /// or in strict mode, so that it always stops at the same row.
+    pub threads: usize,
+}
+
+/// Row and outcome counts of a single input.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct InputSummary {
+    pub input: String,
+    pub rows: u64,
+    pub applied: u64,
+    pub rejected: u64,
+    /// Detailed counts, only included in run statistics.
+    #[serde(skip)]
+    pub stats: TransactionStats,
+}
+
+/// Capacity of the channel to every worker thread and from the reading thread of the async flow.
+const WORKER_QUEUE: usize = 1024;
+
+/// Reason processing of an input stopped.
+#[derive(Debug)]
+pub enum FlowError {
+    /// Row could not be read or parsed.
+    /// `row` is the 1-based index of the row within the input, header is not counted.
+    Input {
+        row: u64,
+        error: InputError,
+    },
+    /// Row was parsed, but is not a valid transaction.
+    Transaction {
+        row: u64,
+        error: TransactionError,
+    },
+    Engine(EngineError),
+}
+
+impl std::fmt::Display for FlowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlowError::Input { row, .. } | FlowError::Transaction { row, .. } => {
+                write!(f, "row {row}")
+            }
+            FlowError::Engine(_) => f.write_str("unable to process transaction"),
+        }
+    }
+}
+
+impl std::error::Error for FlowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FlowError::Input { error, .. } => Some(error),
+            FlowError::Transaction { error, .. } => Some(error),
+            FlowError::Engine(e) => Some(e),
+        }
+    }
+}
+
+impl From<EngineError> for FlowError {
+    fn from(e: EngineError) -> Self {
+        FlowError::Engine(e)
+    }
+}
+
+impl InputSummary {
+    fn count(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Applied => self.applied += 1,
+            Outcome::Rejected(_) => self.rejected += 1,
+        }
+    }
+
+    fn record(
+        &mut self,
+        engine: &Engine,
+        tx: &Transaction,
+        outcome: Outcome,
+    ) -> Result<(), EngineError> {
+        self.count(outcome);
+        self.stats.record(engine, tx, outcome)
+    }
 
-    let mut reader = ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .trim(csv::Trim::All)
-        .from_reader(input);
+    /// Add counts of another summary to this one.
+    pub fn merge(&mut self, other: &InputSummary) {
+        self.rows += other.rows;
+        self.applied += other.applied;
+        self.rejected += other.rejected;
+        self.stats.merge(&other.stats);
+    }
+}
 
-    for record in reader.deserialize() {
-        let tx: TransactionView = record.unwrap();
-        let tx = Transaction::try_from(tx).unwrap();
-        engine.process_transaction(&tx).unwrap();
+impl FlowConfig {
+    fn should_stop(&self, summary: &InputSummary) -> bool {
+        self.strict && summary.rejected > 0
     }
+}
 
-    engine.get_all_clients().unwrap()
+/// Process CSV input, stops at the first malformed row.
+pub fn process_csv_transactions<R: std::io::Read>(
+    engine: &Engine,
+    config: &FlowConfig,
+    input: R,
+) -> Result<InputSummary, FlowError> {
+    process_transactions(engine, config, read_csv(input))
 }
 
-pub fn output_csv_clients<W: std::io::Write>(clients: Vec<ClientView>, output: W) {
-    let mut wtr = WriterBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .from_writer(output);
+/// Process JSON Lines input, stops at the first malformed line.
+pub fn process_json_lines_transactions<R: std::io::Read>(
+    engine: &Engine,
+    config: &FlowConfig,
+    input: R,
+) -> Result<InputSummary, FlowError> {
+    process_transactions(engine, config, read_json_lines(input))
+}
 
-    for client in clients {
-        wtr.serialize(client).unwrap();
+/// Process rows of any input format.
+///
+/// Transactions are only reordered within a single input,
+/// the reorder buffer is flushed at the end of it.
+/// Transactions before a malformed row stay applied.
+pub fn process_transactions<I: Iterator<Item = Result<TransactionView, InputError>>>(
+    engine: &Engine,
+    config: &FlowConfig,
+    rows: I,
+) -> Result<InputSummary, FlowError> {
+    if config.threads > 1 && !config.strict {
+        return process_in_parallel(engine, config, rows);
     }
+    let mut summary = InputSummary::default();
+    let rows = dispatch(config, rows, |tx| {
+        summary.record(engine, &tx, engine.process_transaction(&tx)?)?;
+        Ok(!config.should_stop(&summary))
+    })?;
+    summary.rows = rows;
+    summary.stats.sample_storage(engine)?;
+    Ok(summary)
+}
+
+pub async fn process_csv_transactions_async<D, R>(
+    engine: &AsyncEngine<D>,
+    config: &FlowConfig,
+    input: R,
+) -> Result<InputSummary, FlowError>
+where
+    D: AsyncDatabase,
+    R: std::io::Read + Send + 'static,
+{
+    process_transactions_async(engine, config, read_csv(input)).await
+}
+
+pub async fn process_json_lines_transactions_async<D, R>(
+    engine: &AsyncEngine<D>,
+    config: &FlowConfig,
+    input: R,
+) -> Result<InputSummary, FlowError>
+where
+    D: AsyncDatabase,
+    R: std::io::Read + Send + 'static,
+{
+    process_transactions_async(engine, config, read_json_lines(input)).await
+}
+
+/// Process rows of any input format with the async engine.
+///
+/// Transactions are applied one by one, `config.threads` is ignored
+/// and detailed statistics are not collected.
+/// Rows are read on a blocking thread of the runtime and passed over a channel,
+/// so reading the input does not hold up other tasks.
+pub async fn process_transactions_async<D, I>(
+    engine: &AsyncEngine<D>,
+    config: &FlowConfig,
+    rows: I,
+) -> Result<InputSummary, FlowError>
+where
+    D: AsyncDatabase,
+    I: Iterator<Item = Result<TransactionView, InputError>> + Send + 'static,
+{
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(WORKER_QUEUE);
+    let reader = tokio::task::spawn_blocking(move || {
+        for record in rows {
+            // Receiver is gone once processing stopped early.
+            if sender.blocking_send(record).is_err() {
+                break;
+            }
+        }
+    });
+    let mut reorder = config.reorder_tolerance.map(ReorderBuffer::new);
+    let mut summary = InputSummary::default();
+
+    while let Some(record) = receiver.recv().await {
+        let tx = parse_row(summary.rows + 1, record)?;
+        summary.rows += 1;
+        let ready = match reorder.as_mut() {
+            None => vec![tx],
+            Some(reorder) => reorder.push(tx),
+        };
+        for tx in ready {
+            summary.count(engine.process_transaction(&tx).await?);
+            if config.should_stop(&summary) {
+                return Ok(summary);
+            }
+        }
+    }
+    // Channel is also closed if reading panicked, which must not look like the end of the input.
+    if let Err(e) = reader.await {
+        if e.is_panic() {
+            std::panic::resume_unwind(e.into_panic());
+        }
+    }
+    if let Some(mut reorder) = reorder {
+        for tx in reorder.flush() {
+            summary.count(engine.process_transaction(&tx).await?);
+            if config.should_stop(&summary) {
+                break;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Process transactions on worker threads.
+///
+/// Every client is handled by a single worker,
+/// so transactions of a client are applied in input order,
+/// but there is no order between transactions of different clients.
+/// Workers stop at the first engine error, strict mode is not supported.
+fn process_in_parallel<I: Iterator<Item = Result<TransactionView, InputError>>>(
+    engine: &Engine,
+    config: &FlowConfig,
+    rows: I,
+) -> Result<InputSummary, FlowError> {
+    let stop = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let (senders, workers): (Vec<_>, Vec<_>) = (0..config.threads)
+            .map(|_| {
+                let (sender, receiver) = mpsc::sync_channel::<Transaction>(WORKER_QUEUE);
+                let stop = &stop;
+                let worker = scope.spawn(move || {
+                    let mut summary = InputSummary::default();
+                    for tx in receiver {
+                        let recorded = engine
+                            .process_transaction(&tx)
+                            .and_then(|outcome| summary.record(engine, &tx, outcome));
+                        if recorded.is_err() {
+                            stop.store(true, Ordering::SeqCst);
+                            recorded?;
+                        }
+                    }
+                    Ok::<_, EngineError>(summary)
+                });
+                (sender, worker)
+            })
+            .unzip();
+
+        let rows = dispatch(config, rows, |tx| {
+            let worker = client_stripe(tx.client(), senders.len());
+            Ok(!stop.load(Ordering::SeqCst) && senders[worker].send(tx).is_ok())
+        });
+        drop(senders);
+
+        // Workers are joined before reporting a malformed row, so nothing is left running.
+        let summaries: Vec<_> = workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect();
+        let mut summary = InputSummary {
+            rows: rows?,
+            ..Default::default()
+        };
+        for worker_summary in summaries {
+            summary.merge(&worker_summary?);
+        }
+        summary.stats.sample_storage(engine)?;
+        Ok(summary)
+    })
+}
+
+/// Parse a row into a transaction, `row` is its 1-based index.
+fn parse_row(
+    row: u64,
+    record: Result<TransactionView, InputError>,
+) -> Result<Transaction, FlowError> {
+    let view = record.map_err(|error| FlowError::Input { row, error })?;
+    Transaction::try_from(view).map_err(|error| FlowError::Transaction { row, error })
+}
+
+/// Parse rows and pass transactions to `process` in the order they should be applied.
+///
+/// Stops as soon as `process` returns false. Returns the number of parsed rows.
+fn dispatch<I, F>(config: &FlowConfig, rows: I, mut process: F) -> Result<u64, FlowError>
+where
+    I: Iterator<Item = Result<TransactionView, InputError>>,
+    F: FnMut(Transaction) -> Result<bool, EngineError>,
+{
+    let mut reorder = config.reorder_tolerance.map(ReorderBuffer::new);
+    let mut count = 0;
+
+    for record in rows {
+        let tx = parse_row(count + 1, record)?;
+        count += 1;
+        match reorder.as_mut() {
+            None => {
+                if !process(tx)? {
+                    return Ok(count);
+                }
+            }
+            Some(reorder) => {
+                for tx in reorder.push(tx) {
+                    if !process(tx)? {
+                        return Ok(count);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(mut reorder) = reorder {
+        for tx in reorder.flush() {
+            if !process(tx)? {
+                break;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+pub fn validate_csv_transactions<R: std::io::Read>(
+    validator: &mut Validator,
+    name: &str,
+    input: R,
+) -> Result<InputSummary, FlowError> {
+    validate_transactions(validator, name, read_csv(input))
+}
+
+pub fn validate_json_lines_transactions<R: std::io::Read>(
+    validator: &mut Validator,
+    name: &str,
+    input: R,
+) -> Result<InputSummary, FlowError> {
+    validate_transactions(validator, name, read_json_lines(input))
+}
+
+/// Check rows of any input format, malformed rows are reported instead of stopping.
+/// Input that cannot be read, e.g. a corrupt compressed file, still stops the check.
+fn validate_transactions<I: Iterator<Item = Result<TransactionView, InputError>>>(
+    validator: &mut Validator,
+    name: &str,
+    rows: I,
+) -> Result<InputSummary, FlowError> {
+    let mut summary = InputSummary::default();
+    for record in rows {
+        summary.rows += 1;
+        let record = match record {
+            Err(error) if error.is_io() => {
+                return Err(FlowError::Input {
+                    row: summary.rows,
+                    error,
+                })
+            }
+            record => record,
+        };
+        if let Some(outcome) = validator.check(name, summary.rows, record)? {
+            summary.count(outcome);
+        }
+    }
+    Ok(summary)
+}
+
+pub fn output_clients<C: Serialize, W: std::io::Write>(
+    clients: Vec<ClientView<C>>,
+    output: W,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    write_records(clients, output, format)
+}
+
+pub fn output_statement<C: Serialize, W: std::io::Write>(
+    entries: Vec<StatementEntry<C>>,
+    output: W,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    write_records(entries, output, format)
+}
+
+pub fn output_issues<W: std::io::Write>(
+    issues: Vec<ValidationIssue>,
+    output: W,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    write_records(issues, output, format)
+}
+
+pub fn output_stats<W: std::io::Write>(stats: &RunStats, output: W) -> std::io::Result<()> {
+    write_json_record(stats, output)
+}
+
+pub fn output_csv_flagged<W: std::io::Write>(
+    flagged: Vec<FlaggedClientView>,
+    output: W,
+) -> std::io::Result<()> {
+    write_csv(flagged, output)
+}
+
+pub fn output_summary<W: std::io::Write>(
+    summaries: Vec<InputSummary>,
+    output: W,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    write_records(summaries, output, format)
+}
+
+pub fn output_events<W: std::io::Write>(
+    events: Vec<AuditEvent>,
+    output: W,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    write_records(events, output, format)
 }
 
 #[cfg(test)]
 mod test {
+    use crate::amount;
     use crate::client::ClientView;
-    use crate::flow::process_csv_transactions;
+    use crate::db::InMemoryDB;
+    use crate::engine::Engine;
+    use crate::flow::{
+        process_csv_transactions, process_csv_transactions_async, process_json_lines_transactions,
+        process_transactions_async, FlowConfig, FlowError,
+    };
+    use crate::input::read_csv;
+    use crate::transactions::{Transaction, TransactionError, TransactionView};
+    use crate::types::{ClientID, ErrorChain};
     use csv::ReaderBuilder;
     use std::collections::HashSet;
 
     fn test_sample(input: String, correct_output: String) {
-        let result_set =
-            HashSet::from_iter(process_csv_transactions(&mut input.as_bytes()).into_iter());
+        test_sample_with(
+            &Engine::new(),
+            &FlowConfig::default(),
+            input,
+            correct_output,
+        )
+    }
+
+    fn test_sample_with(
+        engine: &Engine,
+        config: &FlowConfig,
+        input: String,
+        correct_output: String,
+    ) {
+        process_csv_transactions(engine, config, &mut input.as_bytes()).unwrap();
+        let result_set = HashSet::from_iter(engine.get_all_clients().unwrap());
 
         let mut correct_set = HashSet::new();
         let mut correct_buff = correct_output.as_bytes();
@@ -80,6 +514,80 @@ client, available, held, total, locked
         )
     }
 
+    #[test]
+    fn test_flow_client_id_width() {
+        let max = ClientID::MAX;
+        test_sample(
+            format!("type, client, tx, amount\ndeposit, {max}, 1, 1.0"),
+            format!("client, available, held, total, locked\n{max}, 1.0, 0.0, 1.0, false"),
+        );
+        let overflow = u128::from(max) + 1;
+        let input = format!("type, client, tx, amount\ndeposit, {overflow}, 1, 1.0");
+        let rows: Vec<_> = read_csv(input.as_bytes()).collect();
+        assert!(rows[0].is_err());
+    }
+
+    #[test]
+    fn test_flow_errors() {
+        let input = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, x, 2, 1.0
+deposit, 1, 3, 1.0";
+        for threads in [1, 4] {
+            let engine = Engine::new();
+            let config = FlowConfig {
+                threads,
+                ..Default::default()
+            };
+            let error = process_csv_transactions(&engine, &config, input.as_bytes()).unwrap_err();
+            assert!(matches!(error, FlowError::Input { row: 2, .. }));
+            assert_eq!(error.to_string(), "row 2");
+            let message = ErrorChain(&error).to_string();
+            assert!(message.starts_with("row 2: invalid CSV row: "), "{message}");
+            // Rows before the malformed one stay applied.
+            assert_eq!(engine.get_client(&1).unwrap().available, amount!(1.0));
+        }
+
+        let input = "{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":\"1.0\"}\n\
+{\"type\":\"transfer\",\"client\":1,\"tx\":2,\"amount\":\"1.0\"}";
+        let error = process_json_lines_transactions(
+            &Engine::new(),
+            &FlowConfig::default(),
+            input.as_bytes(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &error,
+            FlowError::Transaction {
+                row: 2,
+                error: TransactionError::UnexpectedType(_)
+            }
+        ));
+        assert_eq!(
+            ErrorChain(&error).to_string(),
+            "row 2: unexpected transaction type `transfer`"
+        );
+    }
+
+    #[test]
+    fn test_flow_extreme_amounts() {
+        test_sample(
+            "\
+type, client, tx, amount
+deposit, 1, 1, 79228162514264337593543950335
+withdrawal, 1, 2, -5.0
+deposit, 1, 3, 1000000000000000
+deposit, 1, 4, 1000000000000000
+withdrawal, 1, 5, 0.5"
+                .to_string(),
+            "\
+client, available, held, total, locked
+1, 1999999999999999.5, 0.0, 1999999999999999.5, false"
+                .to_string(),
+        );
+    }
+
     #[test]
     fn test_flow2() {
         let _ = env_logger::try_init();
@@ -129,4 +637,230 @@ client, available, held, total, locked
                 .to_string(),
         )
     }
+
+    #[test]
+    fn test_flow_timestamps() {
+        let _ = env_logger::try_init();
+        // Deposit 3 is out of order, dispute of deposit 1 is too late.
+        let input = "\
+type, client, tx, amount, timestamp
+deposit, 1, 1, 1.0, 100
+deposit, 1, 2, 2.0, 200
+deposit, 1, 3, 4.0, 150
+dispute, 1, 1, , 1200
+dispute, 1, 2, , 1200";
+        test_sample_with(
+            &Engine::builder().with_dispute_window(1000).build(),
+            &FlowConfig::default(),
+            input.to_string(),
+            "\
+client, available, held, total, locked
+1, 1.0, 2.0, 3.0, false"
+                .to_string(),
+        );
+        test_sample_with(
+            &Engine::builder().with_dispute_window(1000).build(),
+            &FlowConfig {
+                reorder_tolerance: Some(60),
+                ..Default::default()
+            },
+            input.to_string(),
+            "\
+client, available, held, total, locked
+1, 5.0, 2.0, 7.0, false"
+                .to_string(),
+        );
+    }
+
+    #[test]
+    fn test_flow_json_lines() {
+        let _ = env_logger::try_init();
+        let csv_input = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 2, 2, 2.0
+dispute, 1, 1,
+withdrawal, 2, 3, 0.5";
+        let json_input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+{"type": "deposit", "client": 2, "tx": 2, "amount": "2.0"}
+{"type": "dispute", "client": 1, "tx": 1, "amount": null}
+{"type": "withdrawal", "client": 2, "tx": 3, "amount": "0.5"}"#;
+        let csv_engine = Engine::new();
+        let csv_summary =
+            process_csv_transactions(&csv_engine, &FlowConfig::default(), csv_input.as_bytes())
+                .unwrap();
+        let json_engine = Engine::new();
+        let json_summary = process_json_lines_transactions(
+            &json_engine,
+            &FlowConfig::default(),
+            json_input.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(csv_summary, json_summary);
+        assert_eq!(
+            HashSet::<ClientView>::from_iter(csv_engine.get_all_clients().unwrap()),
+            HashSet::from_iter(json_engine.get_all_clients().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_flow_multiple_inputs() {
+        let _ = env_logger::try_init();
+        let engine = Engine::new();
+        let day1 = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+withdrawal, 1, 2, 2.0";
+        let day2 = "\
+type, client, tx, amount
+dispute, 1, 1,
+resolve, 1, 1,
+chargeback, 1, 1,";
+        let summary1 =
+            process_csv_transactions(&engine, &FlowConfig::default(), day1.as_bytes()).unwrap();
+        let summary2 =
+            process_csv_transactions(&engine, &FlowConfig::default(), day2.as_bytes()).unwrap();
+        assert_eq!(
+            (summary1.rows, summary1.applied, summary1.rejected),
+            (2, 1, 1)
+        );
+        assert_eq!(
+            (summary2.rows, summary2.applied, summary2.rejected),
+            (3, 2, 1)
+        );
+        assert_eq!(engine.get_client(&1).unwrap().available, amount!(1.0));
+    }
+
+    #[test]
+    fn test_flow_strict() {
+        let _ = env_logger::try_init();
+        let engine = Engine::new();
+        let config = FlowConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let input = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+withdrawal, 1, 2, 2.0
+deposit, 1, 3, 1.0";
+        let summary = process_csv_transactions(&engine, &config, input.as_bytes()).unwrap();
+        assert_eq!((summary.rows, summary.applied, summary.rejected), (2, 1, 1));
+        assert_eq!(engine.get_client(&1).unwrap().available, amount!(1.0));
+    }
+
+    #[test]
+    fn test_flow_threads() {
+        let _ = env_logger::try_init();
+        // Groups of 5 rows of a client, disputes refer to the first deposit of the group.
+        // Odd clients have their last group charged back, everything else is resolved.
+        let mut input = "type, client, tx, amount\n".to_string();
+        for tx in 0..1000 {
+            let group = tx / 5;
+            let client = group % 50;
+            let first = group * 5;
+            match tx % 5 {
+                0 | 1 => input += &format!("deposit, {client}, {tx}, 1.5\n"),
+                2 => input += &format!("withdrawal, {client}, {tx}, 2.0\n"),
+                3 => input += &format!("dispute, {client}, {first},\n"),
+                _ if group >= 150 && client % 2 == 1 => {
+                    input += &format!("chargeback, {client}, {first},\n")
+                }
+                _ => input += &format!("resolve, {client}, {first},\n"),
+            }
+        }
+        let engine = Engine::new();
+        let summary =
+            process_csv_transactions(&engine, &FlowConfig::default(), input.as_bytes()).unwrap();
+        let parallel_engine = Engine::new();
+        let parallel_summary = process_csv_transactions(
+            &parallel_engine,
+            &FlowConfig {
+                threads: 4,
+                ..Default::default()
+            },
+            input.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(summary, parallel_summary);
+        assert_eq!(summary.rejected, 0);
+        assert_eq!(summary.stats.volume.held, amount!(300.0));
+        assert_eq!(summary.stats.volume.charged_back, amount!(37.5));
+        let clients = parallel_engine.get_all_clients().unwrap();
+        assert_eq!(clients.iter().filter(|client| client.locked).count(), 25);
+        assert_eq!(
+            HashSet::<ClientView>::from_iter(engine.get_all_clients().unwrap()),
+            HashSet::from_iter(clients)
+        );
+
+        // Strict mode stops at the same row with any number of threads.
+        let input = input.replace("resolve, 7, 35,", "resolve, 7, 36,");
+        for threads in [1, 4] {
+            let engine = Engine::new();
+            let config = FlowConfig {
+                strict: true,
+                threads,
+                ..Default::default()
+            };
+            let summary = process_csv_transactions(&engine, &config, input.as_bytes()).unwrap();
+            assert_eq!((summary.rows, summary.rejected), (40, 1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flow_async() {
+        let _ = env_logger::try_init();
+        let input = "\
+type, client, tx, amount, timestamp
+deposit, 1, 1, 1.0, 100
+deposit, 2, 2, 2.0, 90
+dispute, 1, 1, , 110
+withdrawal, 2, 3, 0.5, 120
+chargeback, 1, 1, , 130";
+        let config = FlowConfig {
+            reorder_tolerance: Some(60),
+            ..Default::default()
+        };
+        let engine = Engine::new();
+        let summary = process_csv_transactions(&engine, &config, input.as_bytes()).unwrap();
+        let async_engine = Engine::builder().build_async(InMemoryDB::new());
+        let async_summary =
+            process_csv_transactions_async(&async_engine, &config, input.as_bytes())
+                .await
+                .unwrap();
+        assert_eq!(
+            (summary.rows, summary.applied, summary.rejected),
+            (
+                async_summary.rows,
+                async_summary.applied,
+                async_summary.rejected
+            )
+        );
+        assert_eq!(
+            HashSet::<ClientView>::from_iter(engine.get_all_clients().unwrap()),
+            HashSet::from_iter(async_engine.get_all_clients().await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flow_async_stops_reading() {
+        let engine = Engine::builder().build_async(InMemoryDB::new());
+        let config = FlowConfig {
+            strict: true,
+            ..Default::default()
+        };
+        // Endless input, reading must stop once the flow stops at the rejected withdrawal.
+        let deposit = TransactionView::from(&Transaction::deposit(1, 1, amount!(1.0)));
+        let rows = std::iter::once(Ok(deposit)).chain(std::iter::repeat_with(|| {
+            Ok(TransactionView::from(&Transaction::withdrawal(
+                1,
+                2,
+                amount!(5.0),
+            )))
+        }));
+        let summary = process_transactions_async(&engine, &config, rows)
+            .await
+            .unwrap();
+        assert_eq!((summary.rows, summary.applied, summary.rejected), (2, 1, 1));
+    }
 }
@@ -0,0 +1,283 @@
+use crate::types::{ClientID, EngineError};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+/// Action taken against a client once a fraud rule triggers.
+///
+/// Variants are ordered by severity, a client can only be escalated.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum FraudAction {
+    /// Only report the client.
+    Flag,
+    /// Report the client and reject all further withdrawals.
+    #[serde(rename = "restrict")]
+    RestrictWithdrawals,
+    /// Report the client and freeze the account.
+    Freeze,
+}
+
+/// Suspicious pattern with its threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FraudSignal {
+    /// Number of disputes opened by the client reached the threshold.
+    DisputeCount(u64),
+    /// Ratio of disputes to deposits reached the threshold.
+    DisputeRatio(Decimal),
+    /// Number of chargebacks against the client reached the threshold.
+    ChargebackCount(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FraudRule {
+    pub signal: FraudSignal,
+    pub action: FraudAction,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FraudConfig {
+    pub rules: Vec<FraudRule>,
+}
+
+/// Per-client history relevant for fraud detection.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ClientActivity {
+    pub deposits: u64,
+    pub disputes: u64,
+    pub chargebacks: u64,
+    /// Most severe rule triggered so far.
+    pub flag: Option<FraudRule>,
+}
+
+/// Structure to output flagged clients in a specific format.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FlaggedClientView {
+    pub client: ClientID,
+    pub action: FraudAction,
+    pub reason: String,
+    pub deposits: u64,
+    pub disputes: u64,
+    pub chargebacks: u64,
+}
+
+/// Applied transaction types that fraud detection keeps track of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FraudEvent {
+    Deposit,
+    Dispute,
+    Chargeback,
+}
+
+/// Tracks client activity and evaluates configured rules against it.
+///
+/// Does not keep any state if no rules are configured.
+pub struct FraudDetector {
+    config: FraudConfig,
+    activity: RwLock<HashMap<ClientID, ClientActivity>>,
+}
+
+impl FraudRule {
+    fn is_triggered(&self, activity: &ClientActivity) -> bool {
+        match self.signal {
+            FraudSignal::DisputeCount(limit) => activity.disputes >= limit,
+            FraudSignal::DisputeRatio(limit) => {
+                activity.deposits > 0
+                    && Decimal::from(activity.disputes) / Decimal::from(activity.deposits) >= limit
+            }
+            FraudSignal::ChargebackCount(limit) => activity.chargebacks >= limit,
+        }
+    }
+}
+
+impl FraudDetector {
+    pub fn new(config: FraudConfig) -> Self {
+        Self {
+            config,
+            activity: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.rules.is_empty()
+    }
+
+    /// Record an applied event for the client and evaluate the rules.
+    /// Returns an action only if the client got escalated by this event.
+    pub fn record(
+        &self,
+        id: ClientID,
+        event: FraudEvent,
+    ) -> Result<Option<FraudAction>, EngineError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let mut db = self.activity.write().map_err(|_| EngineError::PoisonLock)?;
+        let activity = db.entry(id).or_default();
+        match event {
+            FraudEvent::Deposit => activity.deposits += 1,
+            FraudEvent::Dispute => activity.disputes += 1,
+            FraudEvent::Chargeback => activity.chargebacks += 1,
+        }
+        let current = activity.flag.as_ref().map(|rule| rule.action);
+        let triggered = self
+            .config
+            .rules
+            .iter()
+            .filter(|rule| rule.is_triggered(activity))
+            .filter(|rule| Some(rule.action) > current)
+            .max_by_key(|rule| rule.action);
+        Ok(match triggered {
+            Some(rule) => {
+                log::warn!(target: "fraud", "Client {id} triggered fraud rule {rule}. {activity:?}");
+                activity.flag = Some(rule.clone());
+                Some(rule.action)
+            }
+            None => None,
+        })
+    }
+
+    /// Get a vector of flagged clients in output-friendly form
+    pub fn flagged_clients(&self) -> Result<Vec<FlaggedClientView>, EngineError> {
+        let db = self.activity.read().map_err(|_| EngineError::PoisonLock)?;
+        Ok(db
+            .iter()
+            .filter_map(|(id, activity)| {
+                activity.flag.as_ref().map(|rule| FlaggedClientView {
+                    client: *id,
+                    action: rule.action,
+                    reason: rule.signal.to_string(),
+                    deposits: activity.deposits,
+                    disputes: activity.disputes,
+                    chargebacks: activity.chargebacks,
+                })
+            })
+            .collect())
+    }
+}
+
+impl fmt::Display for FraudSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FraudSignal::DisputeCount(limit) => write!(f, "disputes:{limit}"),
+            FraudSignal::DisputeRatio(limit) => write!(f, "ratio:{limit}"),
+            FraudSignal::ChargebackCount(limit) => write!(f, "chargebacks:{limit}"),
+        }
+    }
+}
+
+impl fmt::Display for FraudAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FraudAction::Flag => write!(f, "flag"),
+            FraudAction::RestrictWithdrawals => write!(f, "restrict"),
+            FraudAction::Freeze => write!(f, "freeze"),
+        }
+    }
+}
+
+impl fmt::Display for FraudRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.signal, self.action)
+    }
+}
+
+/// Parses rules in `<signal>:<threshold>:<action>` form,
+/// e.g. `disputes:3:flag`, `ratio:0.5:restrict` or `chargebacks:1:freeze`.
+impl FromStr for FraudRule {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let (Some(signal), Some(threshold), Some(action), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err("fraud rule must have <signal>:<threshold>:<action> form");
+        };
+        let signal = match signal {
+            "disputes" => {
+                FraudSignal::DisputeCount(threshold.parse().map_err(|_| "invalid dispute count")?)
+            }
+            "ratio" => {
+                FraudSignal::DisputeRatio(threshold.parse().map_err(|_| "invalid dispute ratio")?)
+            }
+            "chargebacks" => FraudSignal::ChargebackCount(
+                threshold.parse().map_err(|_| "invalid chargeback count")?,
+            ),
+            _ => return Err("unexpected fraud signal"),
+        };
+        let action = match action {
+            "flag" => FraudAction::Flag,
+            "restrict" => FraudAction::RestrictWithdrawals,
+            "freeze" => FraudAction::Freeze,
+            _ => return Err("unexpected fraud action"),
+        };
+        Ok(FraudRule { signal, action })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fraud::{
+        FraudAction, FraudConfig, FraudDetector, FraudEvent, FraudRule, FraudSignal,
+    };
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            "ratio:0.5:restrict".parse::<FraudRule>(),
+            Ok(FraudRule {
+                signal: FraudSignal::DisputeRatio(dec!(0.5)),
+                action: FraudAction::RestrictWithdrawals,
+            })
+        );
+        assert_eq!(
+            "chargebacks:1:freeze".parse::<FraudRule>(),
+            Ok(FraudRule {
+                signal: FraudSignal::ChargebackCount(1),
+                action: FraudAction::Freeze,
+            })
+        );
+        assert!("disputes:x:flag".parse::<FraudRule>().is_err());
+        assert!("disputes:1:ban".parse::<FraudRule>().is_err());
+        assert!("disputes:1".parse::<FraudRule>().is_err());
+    }
+
+    #[test]
+    fn test_escalation() {
+        let detector = FraudDetector::new(FraudConfig {
+            rules: vec![
+                "disputes:1:flag".parse().unwrap(),
+                "ratio:0.5:restrict".parse().unwrap(),
+                "disputes:3:freeze".parse().unwrap(),
+            ],
+        });
+        for _ in 0..4 {
+            assert_eq!(detector.record(1, FraudEvent::Deposit).unwrap(), None);
+        }
+        assert_eq!(
+            detector.record(1, FraudEvent::Dispute).unwrap(),
+            Some(FraudAction::Flag)
+        );
+        assert_eq!(
+            detector.record(1, FraudEvent::Dispute).unwrap(),
+            Some(FraudAction::RestrictWithdrawals)
+        );
+        assert_eq!(detector.record(1, FraudEvent::Deposit).unwrap(), None);
+        assert_eq!(
+            detector.record(1, FraudEvent::Dispute).unwrap(),
+            Some(FraudAction::Freeze)
+        );
+        assert_eq!(detector.record(1, FraudEvent::Dispute).unwrap(), None);
+
+        let flagged = detector.flagged_clients().unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].action, FraudAction::Freeze);
+        assert_eq!(flagged[0].reason, "disputes:3");
+        assert_eq!(flagged[0].disputes, 4);
+    }
+}
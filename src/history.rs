@@ -0,0 +1,121 @@
+use crate::audit::AuditEvent;
+use crate::client::{Client, ClientView};
+use crate::types::{Amount, ClientID, EngineError, Timestamp};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Point in the input to query balances at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    /// After the first N transactions of the input were processed.
+    Index(u64),
+    /// After all transactions up to the given time were processed.
+    Time(Timestamp),
+}
+
+/// Client balances right after a processed event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceRecord {
+    /// 1-based index of the input transaction that caused the event.
+    pub index: u64,
+    /// Latest input time known to the engine at the event.
+    pub time: Timestamp,
+    pub event: AuditEvent,
+    /// Amount of the transaction, or of the transaction it refers to, as seen by the engine.
+    pub amount: Option<Amount>,
+    pub available: Amount,
+    pub held: Amount,
+    pub locked: bool,
+}
+
+/// Per-client history of balances.
+///
+/// Records are appended in processing order,
+/// so both `index` and `time` are non-decreasing for every client
+/// and queries are answered with a binary search.
+/// Kept in memory for the lifetime of the engine, it is not persisted.
+#[derive(Default)]
+pub struct BalanceHistory {
+    clients: RwLock<HashMap<ClientID, Vec<BalanceRecord>>>,
+}
+
+impl BalanceRecord {
+    fn is_before(&self, as_of: AsOf) -> bool {
+        match as_of {
+            AsOf::Index(index) => self.index <= index,
+            AsOf::Time(time) => self.time <= time,
+        }
+    }
+
+    pub fn get_view(&self, id: ClientID) -> ClientView {
+        ClientView {
+            client: id,
+            available: self.available,
+            held: self.held,
+            total: self.available.saturating_add(self.held),
+            locked: self.locked,
+        }
+    }
+}
+
+impl BalanceHistory {
+    pub fn record(
+        &self,
+        index: u64,
+        time: Timestamp,
+        event: AuditEvent,
+        amount: Option<Amount>,
+        client: &Client,
+    ) -> Result<(), EngineError> {
+        let mut db = self.clients.write().map_err(|_| EngineError::PoisonLock)?;
+        db.entry(event.client).or_default().push(BalanceRecord {
+            index,
+            time,
+            event,
+            amount,
+            available: client.available,
+            held: client.held,
+            locked: client.is_frozen(),
+        });
+        Ok(())
+    }
+
+    /// Get client balances as of the given point,
+    /// None if the client did not exist at that point.
+    pub fn get(&self, id: &ClientID, as_of: AsOf) -> Result<Option<ClientView>, EngineError> {
+        let db = self.clients.read().map_err(|_| EngineError::PoisonLock)?;
+        Ok(db
+            .get(id)
+            .and_then(|records| Self::find(records, as_of).map(|r| r.get_view(*id))))
+    }
+
+    /// Get balances of all clients that existed at the given point.
+    pub fn get_all(&self, as_of: AsOf) -> Result<Vec<ClientView>, EngineError> {
+        let db = self.clients.read().map_err(|_| EngineError::PoisonLock)?;
+        Ok(db
+            .iter()
+            .filter_map(|(id, records)| Self::find(records, as_of).map(|r| r.get_view(*id)))
+            .collect())
+    }
+
+    /// Get all records of the client in processing order.
+    pub fn records(&self, id: &ClientID) -> Result<Vec<BalanceRecord>, EngineError> {
+        let db = self.clients.read().map_err(|_| EngineError::PoisonLock)?;
+        Ok(db.get(id).cloned().unwrap_or_default())
+    }
+
+    /// Get sorted ids of all clients with records.
+    pub fn client_ids(&self) -> Result<Vec<ClientID>, EngineError> {
+        let db = self.clients.read().map_err(|_| EngineError::PoisonLock)?;
+        let mut ids: Vec<ClientID> = db.keys().copied().collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn find(records: &[BalanceRecord], as_of: AsOf) -> Option<&BalanceRecord> {
+        match records.partition_point(|record| record.is_before(as_of)) {
+            0 => None,
+            n => Some(&records[n - 1]),
+        }
+    }
+}
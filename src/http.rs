@@ -0,0 +1,508 @@
+use crate::engine::Engine;
+use crate::flow::{process_transactions, FlowConfig};
+use crate::history::AsOf;
+use crate::input::read_csv;
+use crate::transactions::{Transaction, TransactionView};
+use crate::types::{ClientID, EngineError, ErrorChain};
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// Largest accepted size of the request line with headers.
+const MAX_HEAD: usize = 16 * 1024;
+/// Largest accepted request body, larger batches have to be split.
+pub const MAX_BODY: usize = 16 * 1024 * 1024;
+/// Time a connection may stay silent before it is closed, so idle clients do not hold workers.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTP request with the body read into memory.
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+/// JSON response.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl Response {
+    fn ok<T: Serialize>(body: &T) -> Self {
+        match serde_json::to_value(body) {
+            Ok(body) => Self { status: 200, body },
+            Err(e) => Self::error(500, format!("unable to serialize response: {e}")),
+        }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Accept connections on `workers` threads and handle them with a shared engine.
+///
+/// Every connection carries a single request and is closed after the response.
+/// Runs until accepting a connection fails.
+pub fn serve(engine: &Engine, listener: &TcpListener, workers: usize) {
+    std::thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => handle_connection(engine, stream),
+                        Err(e) => {
+                            warn!(target: "http", "Unable to accept connection: {e}");
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn handle_connection(engine: &Engine, stream: TcpStream) {
+    if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+        warn!(target: "http", "Unable to set read timeout: {e}");
+        return;
+    }
+    let mut reader = BufReader::new(&stream);
+    let response = match read_request(&mut reader, &stream) {
+        Ok(request) => {
+            info!(target: "http", "{} {}", request.method, request.path);
+            route(engine, &request)
+        }
+        Err(response) => response,
+    };
+    if let Err(e) = write_response(&stream, &response) {
+        warn!(target: "http", "Unable to send response: {e}");
+    }
+}
+
+/// Read a request, or the error response to answer it with.
+fn read_request<R: BufRead, W: Write>(reader: &mut R, mut writer: W) -> Result<Request, Response> {
+    let bad_request = |e: std::io::Error| Response::error(400, e);
+    // Read the request line and headers up to the empty line.
+    let mut head = vec![];
+    loop {
+        let read = reader.read_until(b'\n', &mut head).map_err(bad_request)?;
+        if read == 0 || head.ends_with(b"\r\n\r\n") || head.ends_with(b"\n\n") {
+            break;
+        }
+        if head.len() > MAX_HEAD {
+            return Err(Response::error(400, "request head is too large"));
+        }
+    }
+
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut parsed = httparse::Request::new(&mut headers);
+    match parsed.parse(&head) {
+        Ok(httparse::Status::Complete(_)) => {}
+        Ok(httparse::Status::Partial) => return Err(Response::error(400, "incomplete request")),
+        Err(e) => return Err(Response::error(400, e)),
+    }
+    let header = |name: &str| {
+        parsed
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| String::from_utf8_lossy(header.value).trim().to_string())
+    };
+    let length: u64 = match header("content-length") {
+        Some(length) => length
+            .parse()
+            .map_err(|_| Response::error(400, "invalid content length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY as u64 {
+        return Err(Response::error(
+            413,
+            format!("request body is larger than {MAX_BODY} bytes"),
+        ));
+    }
+    if length > 0 && header("expect").is_some_and(|value| value == "100-continue") {
+        writer
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+            .map_err(bad_request)?;
+    }
+    // Body grows with the data actually sent, not with the announced length.
+    let mut body = vec![];
+    reader
+        .take(length)
+        .read_to_end(&mut body)
+        .map_err(bad_request)?;
+    if (body.len() as u64) < length {
+        return Err(Response::error(400, "incomplete request body"));
+    }
+
+    Ok(Request {
+        method: parsed.method.unwrap_or_default().to_string(),
+        path: parsed.path.unwrap_or_default().to_string(),
+        body,
+    })
+}
+
+fn write_response<W: Write>(mut output: W, response: &Response) -> std::io::Result<()> {
+    let body = response.body.to_string();
+    write!(
+        output,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        response.status,
+        response.reason(),
+        body.len(),
+    )?;
+    output.flush()
+}
+
+/// Dispatch request to the engine.
+///
+/// `POST /transactions` -- apply a single transaction given as JSON.
+/// `POST /transactions/batch` -- apply CSV rows, nothing is applied if any row is malformed.
+/// `GET /clients` -- balances of all clients.
+/// `GET /clients/<id>` -- balances of the client.
+/// Both take `?at_tx=N` or `?at_time=T` for balances at a point in the processed transactions,
+/// answered from the balance history of an engine built `with_balance_history`.
+/// `GET /clients/<id>/transactions` -- stored deposits and withdrawals of the client.
+///
+/// Engine errors are answered with 500.
+pub fn route(engine: &Engine, request: &Request) -> Response {
+    handle(engine, request).unwrap_or_else(|e| {
+        let e = ErrorChain(&e);
+        warn!(target: "http", "Unable to handle {} {}: {e}", request.method, request.path);
+        Response::error(500, e)
+    })
+}
+
+fn handle(engine: &Engine, request: &Request) -> Result<Response, EngineError> {
+    let (path, query) = match request.path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (request.path.as_str(), None),
+    };
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let as_of = match parse_as_of(query) {
+        Ok(Some(_)) if !engine.has_balance_history() => {
+            return Ok(Response::error(400, "balance history is not enabled"))
+        }
+        Ok(as_of) => as_of,
+        Err(e) => return Ok(Response::error(400, e)),
+    };
+    Ok(match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["transactions"]) => submit_transaction(engine, &request.body)?,
+        ("POST", ["transactions", "batch"]) => submit_batch(engine, &request.body),
+        ("GET", ["clients"]) => match as_of {
+            Some(as_of) => Response::ok(&engine.get_all_clients_at(as_of)?),
+            None => Response::ok(&engine.get_all_clients()?),
+        },
+        ("GET", ["clients", id]) => match id.parse::<ClientID>() {
+            Err(_) => Response::error(400, "invalid client id"),
+            Ok(id) => {
+                let client = match as_of {
+                    Some(as_of) => engine.get_client_at(&id, as_of)?,
+                    None => engine.get_client_view(&id)?,
+                };
+                match client {
+                    Some(client) => Response::ok(&client),
+                    None => Response::error(404, "client not found"),
+                }
+            }
+        },
+        ("GET", ["clients", id, "transactions"]) => match id.parse::<ClientID>() {
+            Err(_) => Response::error(400, "invalid client id"),
+            Ok(id) => {
+                let transactions: Vec<TransactionView> = engine
+                    .get_client_transactions(&id)?
+                    .iter()
+                    .map(TransactionView::from)
+                    .collect();
+                Response::ok(&transactions)
+            }
+        },
+        (_, ["transactions"] | ["transactions", "batch"])
+        | (_, ["clients"] | ["clients", _] | ["clients", _, "transactions"]) => {
+            Response::error(405, "method not allowed")
+        }
+        _ => Response::error(404, "not found"),
+    })
+}
+
+/// Point in time of a `at_tx=N` or `at_time=T` query, `None` without a query.
+fn parse_as_of(query: Option<&str>) -> Result<Option<AsOf>, String> {
+    let Some(query) = query.filter(|query| !query.is_empty()) else {
+        return Ok(None);
+    };
+    let (name, value) = query.split_once('=').unwrap_or((query, ""));
+    let invalid = |_| format!("invalid value of {name}");
+    match name {
+        "at_tx" => Ok(Some(AsOf::Index(value.parse().map_err(invalid)?))),
+        "at_time" => Ok(Some(AsOf::Time(value.parse().map_err(invalid)?))),
+        _ => Err(format!(
+            "unexpected query `{query}`, expected at_tx=N or at_time=T"
+        )),
+    }
+}
+
+fn submit_transaction(engine: &Engine, body: &[u8]) -> Result<Response, EngineError> {
+    let view: TransactionView = match serde_json::from_slice(body) {
+        Ok(view) => view,
+        Err(e) => return Ok(Response::error(400, format!("invalid transaction: {e}"))),
+    };
+    Ok(match Transaction::try_from(view) {
+        Ok(tx) => {
+            let outcome = engine.process_transaction(&tx)?;
+            Response::ok(&json!({ "result": outcome }))
+        }
+        Err(e) => Response::error(400, e),
+    })
+}
+
+fn submit_batch(engine: &Engine, body: &[u8]) -> Response {
+    // Parse all rows first, so a malformed batch is not applied partially.
+    let mut transactions = vec![];
+    for (row, record) in read_csv(body).enumerate() {
+        let parsed = record
+            .map_err(|e| ErrorChain(&e).to_string())
+            .and_then(|view| Transaction::try_from(view).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(tx) => transactions.push(TransactionView::from(&tx)),
+            Err(e) => return Response::error(400, format!("row {}: {e}", row + 1)),
+        }
+    }
+    match process_transactions(
+        engine,
+        &FlowConfig::default(),
+        transactions.into_iter().map(Ok),
+    ) {
+        Ok(summary) => Response::ok(&summary),
+        Err(e) => Response::error(500, ErrorChain(&e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::client::Client;
+    use crate::db::Database;
+    use crate::engine::Engine;
+    use crate::http::{route, serve, Request, MAX_BODY};
+    use crate::transactions::Transaction;
+    use crate::types::{ClientID, DatabaseError, TxUID};
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::Arc;
+
+    /// Database that fails every operation.
+    struct BrokenDB;
+
+    impl Database for BrokenDB {
+        fn get_tx(&self, _: &TxUID) -> Result<Option<Transaction>, DatabaseError> {
+            Err(DatabaseError::PoisonLock)
+        }
+        fn write_tx(&self, _: Transaction) -> Result<(), DatabaseError> {
+            Err(DatabaseError::PoisonLock)
+        }
+        fn transaction_count(&self) -> Result<usize, DatabaseError> {
+            Err(DatabaseError::PoisonLock)
+        }
+        fn client_transactions(&self, _: &ClientID) -> Result<Vec<Transaction>, DatabaseError> {
+            Err(DatabaseError::PoisonLock)
+        }
+        fn get_client(&self, _: &ClientID) -> Result<Option<Client>, DatabaseError> {
+            Err(DatabaseError::PoisonLock)
+        }
+        fn write_client(&self, _: ClientID, _: Client) -> Result<(), DatabaseError> {
+            Err(DatabaseError::PoisonLock)
+        }
+        fn all_clients(&self) -> Result<Vec<(ClientID, Client)>, DatabaseError> {
+            Err(DatabaseError::PoisonLock)
+        }
+    }
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn test_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve(&Engine::new(), &listener, 4));
+
+        let deposit = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "3.5"}"#;
+        assert_eq!(
+            request(addr, "POST", "/transactions", deposit),
+            (200, json!({ "result": "applied" }))
+        );
+        let withdrawal = r#"{"type": "withdrawal", "client": 1, "tx": 2, "amount": "5.0"}"#;
+        assert_eq!(
+            request(addr, "POST", "/transactions", withdrawal),
+            (200, json!({ "result": "insufficient_funds" }))
+        );
+        let (status, _) = request(addr, "POST", "/transactions", r#"{"type": "deposit"}"#);
+        assert_eq!(status, 400);
+
+        let batch = "type, client, tx, amount\ndeposit, 2, 3, 2.0\ndispute, 1, 1,\n";
+        let (status, summary) = request(addr, "POST", "/transactions/batch", batch);
+        assert_eq!(status, 200);
+        assert_eq!(
+            (summary["rows"].clone(), summary["applied"].clone()),
+            (json!(2), json!(2))
+        );
+        let (status, _) = request(
+            addr,
+            "POST",
+            "/transactions/batch",
+            "type, client, tx\nbogus, 1, 1\n",
+        );
+        assert_eq!(status, 400);
+
+        assert_eq!(
+            request(addr, "GET", "/clients/1", ""),
+            (
+                200,
+                json!({ "client": 1, "available": "0.0", "held": "3.5", "total": "3.5", "locked": false })
+            )
+        );
+        let (status, clients) = request(addr, "GET", "/clients", "");
+        assert_eq!((status, clients.as_array().unwrap().len()), (200, 2));
+        let (status, transactions) = request(addr, "GET", "/clients/1/transactions", "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            transactions
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|tx| tx["tx"].clone())
+                .collect::<Vec<_>>(),
+            vec![json!(1)]
+        );
+        assert_eq!(request(addr, "GET", "/clients/9", "").0, 404);
+        assert_eq!(request(addr, "DELETE", "/clients/1", "").0, 405);
+        assert_eq!(request(addr, "GET", "/accounts", "").0, 404);
+    }
+
+    #[test]
+    fn test_http_body_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve(&Engine::new(), &listener, 1));
+
+        let too_large = [(MAX_BODY + 1).to_string(), u64::MAX.to_string()];
+        for length in too_large {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "POST /transactions HTTP/1.1\r\nHost: localhost\r\nContent-Length: {length}\r\n\r\n"
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 413 "), "{response}");
+        }
+        // The only worker is still serving.
+        assert_eq!(request(addr, "GET", "/clients", ""), (200, json!([])));
+    }
+
+    #[test]
+    fn test_http_history() {
+        let engine = Engine::builder().with_balance_history().build();
+        let get = |path: &str| {
+            let response = route(
+                &engine,
+                &Request {
+                    method: "GET".to_string(),
+                    path: path.to_string(),
+                    body: vec![],
+                },
+            );
+            (response.status, response.body)
+        };
+        for tx in [
+            Transaction::deposit(1, 1, amount!(3)).at(10),
+            Transaction::deposit(2, 2, amount!(1)).at(20),
+            Transaction::withdrawal(1, 3, amount!(1)).at(30),
+        ] {
+            engine.process_transaction(&tx).unwrap();
+        }
+
+        assert_eq!(get("/clients/1?at_tx=1").1["available"], json!("3"));
+        assert_eq!(get("/clients/1?at_time=30").1["available"], json!("2"));
+        assert_eq!(get("/clients/1").1["available"], json!("2"));
+        assert_eq!(get("/clients/2?at_tx=1").0, 404);
+        let (status, clients) = get("/clients?at_time=20");
+        assert_eq!((status, clients.as_array().unwrap().len()), (200, 2));
+        assert_eq!(get("/clients?at_tx=x").0, 400);
+        assert_eq!(get("/clients?since=1").0, 400);
+
+        let without_history = |path: &str| {
+            let request = Request {
+                method: "GET".to_string(),
+                path: path.to_string(),
+                body: vec![],
+            };
+            route(&Engine::new(), &request)
+        };
+        assert_eq!(without_history("/clients?at_tx=1").status, 400);
+        assert_eq!(without_history("/clients").status, 200);
+    }
+
+    #[test]
+    fn test_http_engine_error() {
+        let engine = Engine::builder().with_database(Arc::new(BrokenDB)).build();
+        let request = |method: &str, path: &str, body: &str| {
+            route(
+                &engine,
+                &Request {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    body: body.as_bytes().to_vec(),
+                },
+            )
+        };
+        let deposit = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "3.5"}"#;
+        let batch = "type, client, tx, amount\ndeposit, 2, 3, 2.0\n";
+        for response in [
+            request("POST", "/transactions", deposit),
+            request("POST", "/transactions/batch", batch),
+            request("GET", "/clients", ""),
+            request("GET", "/clients/1", ""),
+            request("GET", "/clients/1/transactions", ""),
+        ] {
+            assert_eq!(response.status, 500);
+            let error = response.body["error"].as_str().unwrap();
+            assert!(error.ends_with("database lock is poisoned"), "{error}");
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::compression::Compression;
+use crate::transactions::TransactionView;
+use csv::ReaderBuilder;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line, with the same fields as the CSV header.
+    JsonLines,
+}
+
+#[derive(Debug)]
+pub enum InputError {
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::Csv(e) if e.is_io_error() => f.write_str("unable to read input"),
+            InputError::Csv(_) => f.write_str("invalid CSV row"),
+            InputError::Json(_) => f.write_str("invalid JSON line"),
+            InputError::Io(_) => f.write_str("unable to read input"),
+        }
+    }
+}
+
+impl std::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InputError::Csv(e) => Some(e),
+            InputError::Json(e) => Some(e),
+            InputError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl InputError {
+    /// Whether the input could not be read, as opposed to a malformed row.
+    pub fn is_io(&self) -> bool {
+        match self {
+            InputError::Csv(e) => e.is_io_error(),
+            InputError::Json(e) => e.is_io(),
+            InputError::Io(_) => true,
+        }
+    }
+}
+
+impl InputFormat {
+    /// Detect format by file extension, CSV for anything unknown.
+    /// Compression extension is skipped, e.g. `day1.jsonl.gz` is JSON Lines.
+    pub fn from_path(path: &Path) -> Self {
+        let path = match Compression::from_path(path) {
+            Compression::None => path,
+            _ => Path::new(path.file_stem().unwrap_or_default()),
+        };
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl" | "ndjson" | "json") => InputFormat::JsonLines,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::JsonLines),
+            _ => Err("unexpected input format"),
+        }
+    }
+}
+
+/// Rows of CSV input with a header line.
+pub fn read_csv<R: Read>(input: R) -> impl Iterator<Item = Result<TransactionView, InputError>> {
+    read_csv_as(input)
+}
+
+/// Rows of CSV input with clients of type `C`, e.g. `String` for external references.
+///
+/// A header that cannot be read, e.g. of a corrupt compressed input, is the only row.
+pub fn read_csv_as<R: Read, C: DeserializeOwned>(
+    input: R,
+) -> impl Iterator<Item = Result<TransactionView<C>, InputError>> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(input);
+    // `into_deserialize` would skip a failed header read and end without rows.
+    let (error, rows) = match reader.headers() {
+        Ok(header) => (None, Some((header.clone(), reader))),
+        Err(e) => (Some(InputError::Csv(e)), None),
+    };
+    error
+        .map(Err)
+        .into_iter()
+        .chain(rows.into_iter().flat_map(|(header, reader)| {
+            reader.into_records().map(move |record| {
+                record
+                    .and_then(|record| record.deserialize(Some(&header)))
+                    .map_err(InputError::Csv)
+            })
+        }))
+}
+
+/// Rows of JSON Lines input, empty lines are skipped.
+pub fn read_json_lines<R: Read>(
+    input: R,
+) -> impl Iterator<Item = Result<TransactionView, InputError>> {
+    read_json_lines_as(input)
+}
+
+/// Rows of JSON Lines input with clients of type `C`, e.g. `String` for external references.
+pub fn read_json_lines_as<R: Read, C: DeserializeOwned>(
+    input: R,
+) -> impl Iterator<Item = Result<TransactionView<C>, InputError>> {
+    BufReader::new(input)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            let line = line.map_err(InputError::Io)?;
+            serde_json::from_str(&line).map_err(InputError::Json)
+        })
+}
+
+/// Expand command line arguments into a list of input files.
+///
+/// Directories are replaced with the files they contain and glob patterns
+/// with the paths they match, both sorted by name.
+/// Everything else is kept as is, in the original order.
+/// A file named more than once is only kept at its first position,
+/// a glob pattern that matches nothing is an error.
+pub fn expand_inputs(args: &[String]) -> std::io::Result<Vec<PathBuf>> {
+    let mut inputs = vec![];
+    for arg in args {
+        let path = PathBuf::from(arg);
+        if path.is_dir() {
+            let mut files = vec![];
+            for entry in std::fs::read_dir(&path)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    files.push(entry.path());
+                }
+            }
+            files.sort();
+            inputs.extend(files);
+        } else if arg.contains(['*', '?', '[']) {
+            let paths = glob::glob(arg).map_err(std::io::Error::other)?;
+            let mut files = paths
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(std::io::Error::other)?;
+            if files.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("no files match {arg}"),
+                ));
+            }
+            files.sort();
+            inputs.extend(files);
+        } else {
+            inputs.push(path);
+        }
+    }
+    // Missing files are kept, opening them reports the error with their name.
+    let mut seen = HashSet::new();
+    inputs.retain(|path| seen.insert(std::fs::canonicalize(path).unwrap_or_else(|_| path.clone())));
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::compression::{decompress, CompressedWriter, Compression};
+    use crate::input::{expand_inputs, read_csv, read_json_lines, InputFormat};
+    use std::io::Write;
+    use std::path::Path;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            InputFormat::from_path(Path::new("day1.jsonl")),
+            InputFormat::JsonLines
+        );
+        assert_eq!(
+            InputFormat::from_path(Path::new("day1.csv")),
+            InputFormat::Csv
+        );
+        assert_eq!(InputFormat::from_path(Path::new("day1")), InputFormat::Csv);
+        assert_eq!(
+            InputFormat::from_path(Path::new("archive/day1.jsonl.zst")),
+            InputFormat::JsonLines
+        );
+        assert_eq!(
+            InputFormat::from_path(Path::new("archive/day1.csv.gz")),
+            InputFormat::Csv
+        );
+    }
+
+    #[test]
+    fn test_read_json_lines() {
+        let input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}
+
+{"type": "deposit", "client": 1, "tx": 2, "amount": 2.25, "timestamp": 100}
+{"type": "dispute", "client": 1, "tx": 1}
+{"type": "dispute", "client": 1"#;
+        let rows: Vec<_> = read_json_lines(input.as_bytes()).collect();
+        assert_eq!(rows.len(), 4);
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!(first.amount, Some(amount!(1.5)));
+        let second = rows[1].as_ref().unwrap();
+        assert_eq!(second.amount, Some(amount!(2.25)));
+        assert_eq!(second.timestamp, Some(100));
+        let third = rows[2].as_ref().unwrap();
+        assert_eq!(third.type_str, "dispute");
+        assert_eq!(third.amount, None);
+        assert!(rows[3].is_err());
+    }
+
+    #[test]
+    fn test_read_truncated_input() {
+        let mut writer = CompressedWriter::new(vec![], Compression::Gzip).unwrap();
+        writer
+            .write_all(b"type, client, tx, amount\ndeposit, 1, 1, 1.0\n")
+            .unwrap();
+        let compressed = writer.finish().unwrap();
+        for length in [10, compressed.len() - 10] {
+            let input = decompress(&compressed[..length]).unwrap();
+            let rows: Vec<_> = read_csv(input).collect();
+            assert!(rows.iter().any(|row| row.is_err()), "{length}");
+            let error = rows.into_iter().find_map(Result::err).unwrap();
+            assert!(error.is_io(), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_expand_inputs() {
+        let dir = std::env::temp_dir().join(format!("payments-inputs-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("month")).unwrap();
+        for name in [
+            "month/02.csv",
+            "month/01.csv",
+            "month/03.jsonl",
+            "extra.csv",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let dir_str = dir.to_str().unwrap();
+        let inputs = expand_inputs(&[
+            format!("{dir_str}/extra.csv"),
+            format!("{dir_str}/month/*.csv"),
+            format!("{dir_str}/month"),
+            format!("{dir_str}/month/../extra.csv"),
+            format!("{dir_str}/extra.csv"),
+        ])
+        .unwrap();
+        let unmatched = expand_inputs(&[format!("{dir_str}/month/*.json")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        // Every file is processed once, at its first position.
+        assert_eq!(
+            inputs,
+            vec![
+                dir.join("extra.csv"),
+                dir.join("month/01.csv"),
+                dir.join("month/02.csv"),
+                dir.join("month/03.jsonl"),
+            ]
+        );
+        assert_eq!(unmatched.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+}
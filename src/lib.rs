@@ -0,0 +1,64 @@
+//! Payments engine: applies deposits, withdrawals and disputes to client accounts.
+//!
+//! `Engine` is configured with `EngineBuilder` and applies `Transaction`s one by one,
+//! reporting an `Outcome` for every one of them.
+//! `AsyncEngine` applies the same rules over an `AsyncDatabase`.
+//! `process_csv_transactions` and `process_json_lines_transactions` apply a whole input.
+//! `ClientRegistry` maps external client references to the numeric ids the engine uses.
+//!
+//! Front ends used by the binary are available as modules.
+
+mod amount;
+mod async_engine;
+mod audit;
+mod client;
+mod db;
+mod engine;
+mod expiry;
+mod fraud;
+mod history;
+mod policy;
+mod registry;
+mod reorder;
+mod statement;
+mod transactions;
+mod types;
+
+pub mod compression;
+pub mod flow;
+pub mod http;
+pub mod input;
+pub mod output;
+pub mod stats;
+pub mod stream;
+pub mod validate;
+
+pub use amount::{Amount, AmountError};
+pub use async_engine::AsyncEngine;
+pub use audit::AuditEvent;
+pub use client::{Client, ClientStatus, ClientView};
+pub use db::{AsyncDatabase, Database, InMemoryDB, TransactionRecord};
+pub use engine::{Engine, EngineBuilder, EventHook};
+pub use expiry::{DisputeTimeout, DisputeTimeoutPolicy};
+pub use flow::{
+    process_csv_transactions, process_json_lines_transactions, FlowConfig, FlowError, InputSummary,
+};
+pub use fraud::{FlaggedClientView, FraudAction, FraudConfig, FraudRule, FraudSignal};
+pub use history::AsOf;
+pub use policy::{
+    DisputePolicy, EnginePolicy, FrozenPolicy, IdUniqueness, PolicyError, ValidationRules,
+};
+pub use registry::ClientRegistry;
+pub use statement::StatementEntry;
+pub use transactions::{
+    ChargebackTransaction, DepositTransaction, DisputeTransaction, ResolveTransaction, Transaction,
+    TransactionError, TransactionView, WithdrawalTransaction,
+};
+pub use types::{
+    ClientError, ClientID, DatabaseError, EngineError, ErrorChain, Outcome, Rejection, Timestamp,
+    TxID, TxUID,
+};
+
+/// Used by `amount!`.
+#[doc(hidden)]
+pub use rust_decimal_macros::dec as __dec;
@@ -1,27 +1,685 @@
-mod client;
-mod db;
-mod engine;
-mod flow;
-mod transactions;
-mod types;
-
-use log::info;
-
-fn main() {
-    let _ = env_logger::try_init();
-    let args: Vec<String> = std::env::args().collect();
-    match args.get(1) {
-        None => {
-            info!(target: "main", "Reading data from stdin.");
-            flow::output_csv_clients(
-                flow::process_csv_transactions(std::io::stdin()),
-                std::io::stdout(),
-            );
+use clap::{Args, Parser, Subcommand};
+use log::{error, info, LevelFilter};
+use payments::compression::{self, CompressedWriter, Compression};
+use payments::flow::{self, FlowConfig, FlowError, InputSummary};
+use payments::input::{self, InputError, InputFormat};
+use payments::output::OutputFormat;
+use payments::stats::RunStats;
+use payments::validate::Validator;
+use payments::{
+    http, stream, AsOf, ClientID, ClientRegistry, ClientView, DisputeTimeout, DisputeTimeoutPolicy,
+    Engine, EngineBuilder, EngineError, EnginePolicy, ErrorChain, FraudConfig, FraudRule,
+    InMemoryDB, Timestamp, TransactionView,
+};
+use std::io::{BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Every transaction was applied.
+const EXIT_SUCCESS: u8 = 0;
+/// Processing could not be completed, no output was written.
+const EXIT_FATAL: u8 = 1;
+/// All inputs were processed, but some transactions were rejected.
+const EXIT_REJECTED: u8 = 2;
+
+/// Payments engine: applies deposits, withdrawals and disputes to client accounts.
+///
+/// Without a subcommand behaves as `process`.
+///
+/// Exit codes: 0 if every transaction was applied, 2 if some were rejected,
+/// 1 on fatal errors (malformed input, I/O errors, a rejection in strict mode).
+#[derive(Parser, Debug)]
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Log level (off, error, warn, info, debug, trace), overrides RUST_LOG.
+    #[arg(long, global = true)]
+    log_level: Option<LevelFilter>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Process transactions and print final client balances.
+    Process(RunArgs),
+    /// Check inputs without side effects and print malformed rows, duplicate ids,
+    /// dangling references and rejected transactions instead of balances.
+    Validate(RunArgs),
+    /// Print every transaction affecting the clients with running balances.
+    Statement(StatementArgs),
+    /// Print client balances at a point in the input.
+    ///
+    /// The balance history is not persisted, the inputs are processed again on every run,
+    /// `serve --balance-history` answers the same queries without reprocessing.
+    Replay(ReplayArgs),
+    /// Process the inputs, if any, and serve the engine over HTTP.
+    Serve(ServeArgs),
+    /// Process the inputs, if any, and accept transaction streams over TCP.
+    Stream(StreamArgs),
+    /// Print run statistics as JSON.
+    Stats(RunArgs),
+}
+
+/// Options shared by all subcommands.
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// Input files, directories or glob patterns, stdin if none are given.
+    inputs: Vec<String>,
+
+    /// Input format, detected by file extension by default.
+    #[arg(long, value_name = "csv|jsonl")]
+    input_format: Option<InputFormat>,
+
+    /// Write output to a file instead of stdout, compressed if it ends with .gz or .zst.
+    #[arg(long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Output format.
+    #[arg(long, value_name = "csv|json|jsonl", default_value = "csv")]
+    format: OutputFormat,
+
+    /// Stop at the first rejected transaction and exit with a fatal error.
+    #[arg(long)]
+    strict: bool,
+
+    /// Number of worker threads, transactions are routed to them by client.
+    /// Ignored with --strict, which processes transactions on a single thread.
+    #[arg(long, default_value = "1", conflicts_with = "dispute_timeout")]
+    threads: NonZeroUsize,
+
+    /// TOML or JSON (`.json` extension) file with business rules of the engine.
+    #[arg(long, value_name = "FILE", value_parser = EnginePolicy::from_file)]
+    policy: Option<EnginePolicy>,
+
+    /// Fraud rule in <signal>:<threshold>:<action> form, can be repeated.
+    #[arg(long, value_name = "RULE")]
+    fraud_rule: Vec<FraudRule>,
+
+    /// Write flagged clients to a file.
+    #[arg(long, value_name = "FILE")]
+    flagged_output: Option<String>,
+
+    /// Reject disputes that come more than this many seconds after the deposit.
+    #[arg(long, value_name = "SECONDS")]
+    dispute_window: Option<Timestamp>,
+
+    /// Close disputes that stay open for this many seconds.
+    #[arg(long, value_name = "SECONDS")]
+    dispute_timeout: Option<Timestamp>,
+
+    /// How disputes are closed on timeout.
+    #[arg(long, value_name = "resolve|chargeback", default_value = "resolve")]
+    dispute_timeout_policy: DisputeTimeoutPolicy,
+
+    /// Write the outcome of every transaction to a file.
+    #[arg(long, value_name = "FILE")]
+    audit_output: Option<String>,
+
+    /// Sort transactions by timestamp, allowing them to be late by this many seconds.
+    #[arg(long, value_name = "SECONDS")]
+    reorder_tolerance: Option<Timestamp>,
+
+    /// Print row and outcome counts of every input to stderr.
+    #[arg(long)]
+    summary: bool,
+
+    /// Write run statistics as JSON to a file, `-` for stderr.
+    #[arg(long, value_name = "FILE")]
+    stats: Option<String>,
+
+    /// Client column holds external references, e.g. UUIDs, mapped to numeric ids
+    /// while processing and written back on output.
+    /// Not supported by validate, serve and stream.
+    #[arg(long, conflicts_with_all = ["flagged_output", "audit_output"])]
+    external_client_ids: bool,
+}
+
+#[derive(Args, Debug)]
+struct StatementArgs {
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Only output the given client.
+    #[arg(long, value_name = "ID")]
+    client: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Number of threads handling requests.
+    #[arg(long, value_name = "N", default_value = "4")]
+    workers: NonZeroUsize,
+
+    /// Keep balance history in memory to answer `?at_tx=N` and `?at_time=T` queries.
+    #[arg(long)]
+    balance_history: bool,
+}
+
+#[derive(Args, Debug)]
+struct StreamArgs {
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Address to listen on.
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:9090")]
+    listen: String,
+}
+
+#[derive(Args, Debug)]
+struct ReplayArgs {
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Only output the given client.
+    #[arg(long, value_name = "ID")]
+    client: Option<String>,
+
+    /// Balances after the first N transactions.
+    #[arg(
+        long,
+        value_name = "N",
+        required_unless_present = "at_time",
+        conflicts_with_all = ["at_time", "threads"]
+    )]
+    at_tx: Option<u64>,
+
+    /// Balances after all transactions up to the timestamp.
+    #[arg(long, value_name = "TIMESTAMP")]
+    at_time: Option<Timestamp>,
+}
+
+impl RunArgs {
+    fn engine(&self) -> EngineBuilder {
+        let mut engine = Engine::builder()
+            .with_policy(self.policy.clone().unwrap_or_default())
+            .with_fraud_config(FraudConfig {
+                rules: self.fraud_rule.clone(),
+            });
+        if let Some(window) = self.dispute_window {
+            engine = engine.with_dispute_window(window);
+        }
+        if let Some(period) = self.dispute_timeout {
+            engine = engine.with_dispute_timeout(DisputeTimeout {
+                period,
+                policy: self.dispute_timeout_policy,
+            });
         }
+        if self.audit_output.is_some() {
+            engine = engine.with_audit_log();
+        }
+        engine
+    }
+
+    fn flow_config(&self) -> FlowConfig {
+        FlowConfig {
+            reorder_tolerance: self.reorder_tolerance,
+            strict: self.strict,
+            threads: self.threads.get(),
+        }
+    }
+
+    /// Open the output, compressing it if the file name asks for it.
+    fn open_output(&self) -> Result<CompressedWriter<Box<dyn Write>>, CliError> {
+        let (writer, compression): (Box<dyn Write>, Compression) = match &self.output {
+            None => (Box::new(std::io::stdout()), Compression::None),
+            Some(filename) => {
+                info!(target: "main", "Writing output to {filename}");
+                let path = std::path::Path::new(filename);
+                (
+                    Box::new(create_file(filename)?),
+                    Compression::from_path(path),
+                )
+            }
+        };
+        CompressedWriter::new(writer, compression).map_err(|e| self.output_error(e))
+    }
+
+    fn output_error(&self, e: std::io::Error) -> CliError {
+        CliError::Output(self.output.as_deref().unwrap_or("stdout").to_string(), e)
+    }
+
+    /// Fail for commands that only work with numeric client ids.
+    fn numeric_client_ids(&self, command: &'static str) -> Result<(), CliError> {
+        match self.external_client_ids {
+            true => Err(CliError::Unsupported("--external-client-ids", command)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Fatal error of a command, reported to the user before exiting with `EXIT_FATAL`.
+#[derive(Debug)]
+enum CliError {
+    /// Input could not be found or opened, holds its name.
+    Input(String, std::io::Error),
+    /// Input could not be processed, holds its name.
+    Flow(String, FlowError),
+    /// Output could not be written, holds its name.
+    Output(String, std::io::Error),
+    Engine(EngineError),
+    /// Server could not be started, holds the listen address.
+    Listen(String, std::io::Error),
+    Runtime(std::io::Error),
+    /// Client given on the command line is not a numeric id.
+    Client(String),
+    /// Option is not supported by the command, holds both names.
+    Unsupported(&'static str, &'static str),
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Input(name, _) => write!(f, "unable to read {name}"),
+            CliError::Flow(name, _) => f.write_str(name),
+            CliError::Output(name, _) => write!(f, "unable to write {name}"),
+            CliError::Engine(_) => f.write_str("unable to query the engine"),
+            CliError::Listen(address, _) => write!(f, "unable to listen on {address}"),
+            CliError::Runtime(_) => f.write_str("unable to start async runtime"),
+            CliError::Client(client) => write!(f, "invalid client id `{client}`"),
+            CliError::Unsupported(option, command) => {
+                write!(f, "{option} is not supported by {command}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliError::Input(_, e) | CliError::Output(_, e) | CliError::Listen(_, e) => Some(e),
+            CliError::Runtime(e) => Some(e),
+            CliError::Flow(_, e) => Some(e),
+            CliError::Engine(e) => Some(e),
+            CliError::Client(_) | CliError::Unsupported(..) => None,
+        }
+    }
+}
+
+impl From<EngineError> for CliError {
+    fn from(e: EngineError) -> Self {
+        CliError::Engine(e)
+    }
+}
+
+fn create_file(filename: &str) -> Result<std::io::BufWriter<std::fs::File>, CliError> {
+    std::fs::File::create(filename)
+        .map(std::io::BufWriter::new)
+        .map_err(|e| CliError::Output(filename.to_string(), e))
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            // Clap exits with 2 on usage errors, which means partial rejection here.
+            let _ = e.print();
+            return ExitCode::from(if e.use_stderr() {
+                EXIT_FATAL
+            } else {
+                EXIT_SUCCESS
+            });
+        }
+    };
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = cli.log_level {
+        logger.filter_level(level);
+    }
+    let _ = logger.try_init();
+
+    let command = cli.command.unwrap_or(Command::Process(cli.run));
+    // Panics are bugs, the default hook prints them to stderr.
+    match std::panic::catch_unwind(|| run(command)) {
+        Ok(Ok(code)) => ExitCode::from(code),
+        Ok(Err(e)) => {
+            eprintln!("error: {}", ErrorChain(&e));
+            ExitCode::from(EXIT_FATAL)
+        }
+        Err(_) => ExitCode::from(EXIT_FATAL),
+    }
+}
+
+/// Run the command and return the exit code.
+fn run(command: Command) -> Result<u8, CliError> {
+    let (args, engine) = match &command {
+        Command::Validate(args) => return validate(args),
+        Command::Serve(args) => return serve(args),
+        Command::Stream(args) => return stream(args),
+        Command::Process(args) | Command::Stats(args) => (args, args.engine().build()),
+        Command::Statement(StatementArgs { run, .. }) | Command::Replay(ReplayArgs { run, .. }) => {
+            (run, run.engine().with_balance_history().build())
+        }
+    };
+
+    let config = args.flow_config();
+    let mut registry = args.external_client_ids.then(ClientRegistry::new);
+    let started = Instant::now();
+    let summaries = read_inputs(args, |_, format, reader| {
+        let rows = read_rows(format, reader, registry.as_mut());
+        flow::process_transactions(&engine, &config, rows)
+    })?;
+    let registry = registry.as_ref();
+    let stats = RunStats::new(&engine, summaries, started.elapsed())?;
+    if args.strict && stats.rejected > 0 {
+        error!(target: "main", "Stopped at the first rejected transaction in strict mode.");
+        return Ok(EXIT_FATAL);
+    }
+
+    let mut destination = args.open_output()?;
+    let written = match &command {
+        Command::Process(_) => output_clients(
+            engine.get_all_clients()?,
+            registry,
+            &mut destination,
+            args.format,
+        ),
+        Command::Validate(_) | Command::Serve(_) | Command::Stream(_) => {
+            unreachable!("handled before processing the inputs")
+        }
+        Command::Stats(_) => flow::output_stats(&stats, &mut destination),
+        Command::Statement(StatementArgs { client, .. }) => {
+            let entries = match client {
+                Some(client) => match client_id(client, registry)? {
+                    Some(id) => engine.get_statement(&id)?,
+                    None => vec![],
+                },
+                None => engine.get_all_statements()?,
+            };
+            match registry {
+                Some(registry) => flow::output_statement(
+                    entries
+                        .into_iter()
+                        .map(|e| registry.external_entry(e))
+                        .collect(),
+                    &mut destination,
+                    args.format,
+                ),
+                None => flow::output_statement(entries, &mut destination, args.format),
+            }
+        }
+        Command::Replay(replay) => {
+            let as_of = match (replay.at_tx, replay.at_time) {
+                (Some(index), _) => AsOf::Index(index),
+                (None, Some(time)) => AsOf::Time(time),
+                (None, None) => unreachable!("replay requires --at-tx or --at-time"),
+            };
+            let clients = match &replay.client {
+                Some(client) => match client_id(client, registry)? {
+                    Some(id) => engine.get_client_at(&id, as_of)?.into_iter().collect(),
+                    None => vec![],
+                },
+                None => engine.get_all_clients_at(as_of)?,
+            };
+            output_clients(clients, registry, &mut destination, args.format)
+        }
+    };
+    written
+        .and_then(|_| destination.finish())
+        .map_err(|e| args.output_error(e))?;
+
+    if args.summary {
+        flow::output_summary(stats.inputs.clone(), std::io::stderr(), OutputFormat::Csv)
+            .map_err(|e| CliError::Output("stderr".to_string(), e))?;
+    }
+    match args.stats.as_deref() {
+        None => {}
+        Some("-") => flow::output_stats(&stats, std::io::stderr())
+            .map_err(|e| CliError::Output("stderr".to_string(), e))?,
         Some(filename) => {
-            info!(target: "main", "Reading data from {filename}");
-            let reader = std::io::BufReader::new(std::fs::File::open(filename).unwrap());
-            flow::output_csv_clients(flow::process_csv_transactions(reader), std::io::stdout());
+            info!(target: "main", "Writing run statistics to {filename}");
+            flow::output_stats(&stats, create_file(filename)?)
+                .map_err(|e| CliError::Output(filename.to_string(), e))?;
+        }
+    }
+    if let Some(filename) = &args.flagged_output {
+        info!(target: "main", "Writing flagged clients to {filename}");
+        flow::output_csv_flagged(engine.get_flagged_clients()?, create_file(filename)?)
+            .map_err(|e| CliError::Output(filename.to_string(), e))?;
+    }
+    if let Some(filename) = &args.audit_output {
+        info!(target: "main", "Writing audit log to {filename}");
+        let events = engine.get_audit_log()?;
+        flow::output_events(events, create_file(filename)?, OutputFormat::Csv)
+            .map_err(|e| CliError::Output(filename.to_string(), e))?;
+    }
+
+    Ok(if stats.rejected > 0 {
+        EXIT_REJECTED
+    } else {
+        EXIT_SUCCESS
+    })
+}
+
+/// Process the inputs and serve the engine over HTTP until the listener fails.
+fn serve(args: &ServeArgs) -> Result<u8, CliError> {
+    args.run.numeric_client_ids("serve")?;
+    let mut engine = args.run.engine();
+    if args.balance_history {
+        engine = engine.with_balance_history();
+    }
+    let engine = preload(&args.run, engine.build())?;
+    let listener = std::net::TcpListener::bind(&args.listen)
+        .map_err(|e| CliError::Listen(args.listen.clone(), e))?;
+    info!(target: "main", "Listening on {}", args.listen);
+    http::serve(&engine, &listener, args.workers.get());
+    Ok(EXIT_FATAL)
+}
+
+/// Process the inputs and accept transaction streams until the listener fails.
+fn stream(args: &StreamArgs) -> Result<u8, CliError> {
+    args.run.numeric_client_ids("stream")?;
+    let runtime = tokio::runtime::Runtime::new().map_err(CliError::Runtime)?;
+    let engine = Arc::new(args.run.engine().build_async(InMemoryDB::new()));
+    let config = args.run.flow_config();
+    if !args.run.inputs.is_empty() {
+        read_inputs(&args.run, |_, format, reader| match format {
+            InputFormat::Csv => runtime.block_on(flow::process_csv_transactions_async(
+                &engine, &config, reader,
+            )),
+            InputFormat::JsonLines => runtime.block_on(
+                flow::process_json_lines_transactions_async(&engine, &config, reader),
+            ),
+        })?;
+    }
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(&args.listen)
+            .await
+            .map_err(|e| CliError::Listen(args.listen.clone(), e))?;
+        info!(target: "main", "Listening on {}", args.listen);
+        stream::serve(engine, listener).await;
+        Ok(EXIT_FATAL)
+    })
+}
+
+/// Process the inputs, if any, with the engine of a long-running server.
+fn preload(args: &RunArgs, engine: Engine) -> Result<Engine, CliError> {
+    let config = args.flow_config();
+    if !args.inputs.is_empty() {
+        read_inputs(args, |_, format, reader| match format {
+            InputFormat::Csv => flow::process_csv_transactions(&engine, &config, reader),
+            InputFormat::JsonLines => {
+                flow::process_json_lines_transactions(&engine, &config, reader)
+            }
+        })?;
+    }
+    Ok(engine)
+}
+
+/// Check inputs against a throwaway engine and write found issues instead of balances.
+fn validate(args: &RunArgs) -> Result<u8, CliError> {
+    args.numeric_client_ids("validate")?;
+    let mut validator = Validator::new(args.engine().build());
+    let summaries = read_inputs(args, |name, format, reader| match format {
+        InputFormat::Csv => flow::validate_csv_transactions(&mut validator, name, reader),
+        InputFormat::JsonLines => {
+            flow::validate_json_lines_transactions(&mut validator, name, reader)
         }
+    })?;
+    let issues = validator.into_issues();
+    let found = !issues.is_empty();
+
+    let mut destination = args.open_output()?;
+    flow::output_issues(issues, &mut destination, args.format)
+        .and_then(|_| destination.finish())
+        .map_err(|e| args.output_error(e))?;
+    if args.summary {
+        flow::output_summary(summaries, std::io::stderr(), OutputFormat::Csv)
+            .map_err(|e| CliError::Output("stderr".to_string(), e))?;
+    }
+
+    Ok(if found { EXIT_REJECTED } else { EXIT_SUCCESS })
+}
+
+/// Rows of the input, client references are interned if a registry is given.
+fn read_rows(
+    format: InputFormat,
+    reader: Box<dyn Read + Send>,
+    registry: Option<&mut ClientRegistry>,
+) -> Box<dyn Iterator<Item = Result<TransactionView, InputError>> + '_> {
+    match (format, registry) {
+        (InputFormat::Csv, None) => Box::new(input::read_csv(reader)),
+        (InputFormat::JsonLines, None) => Box::new(input::read_json_lines(reader)),
+        (InputFormat::Csv, Some(registry)) => {
+            Box::new(registry.intern_rows(input::read_csv_as(reader)))
+        }
+        (InputFormat::JsonLines, Some(registry)) => {
+            Box::new(registry.intern_rows(input::read_json_lines_as(reader)))
+        }
+    }
+}
+
+/// Id of the client given on the command line, `None` for an unknown reference.
+fn client_id(
+    client: &str,
+    registry: Option<&ClientRegistry>,
+) -> Result<Option<ClientID>, CliError> {
+    match registry {
+        Some(registry) => Ok(registry.get(client)),
+        None => match client.parse() {
+            Ok(id) => Ok(Some(id)),
+            Err(_) => Err(CliError::Client(client.to_string())),
+        },
+    }
+}
+
+/// Write client balances, with their references if the inputs had them.
+fn output_clients<W: Write>(
+    clients: Vec<ClientView>,
+    registry: Option<&ClientRegistry>,
+    output: W,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    match registry {
+        Some(registry) => flow::output_clients(
+            clients
+                .into_iter()
+                .map(|c| registry.external_client(c))
+                .collect(),
+            output,
+            format,
+        ),
+        None => flow::output_clients(clients, output, format),
+    }
+}
+
+/// Read all inputs in order, stdin if none are given.
+///
+/// `process` gets the input name, its format and the decompressed input.
+/// Stops at the first input that could not be processed.
+fn read_inputs<F>(args: &RunArgs, mut process: F) -> Result<Vec<InputSummary>, CliError>
+where
+    F: FnMut(&str, InputFormat, Box<dyn Read + Send>) -> Result<InputSummary, FlowError>,
+{
+    let mut summaries = vec![];
+    if args.inputs.is_empty() {
+        info!(target: "main", "Reading data from stdin.");
+        // Locked stdin cannot be moved to the reading thread of the async flow.
+        let reader = compression::decompress(BufReader::new(std::io::stdin()))
+            .map_err(|e| CliError::Input("stdin".to_string(), e))?;
+        let summary = process("-", args.input_format.unwrap_or_default(), reader)
+            .map_err(|e| CliError::Flow("stdin".to_string(), e))?;
+        summaries.push(InputSummary {
+            input: "-".to_string(),
+            ..summary
+        });
+    }
+    let paths = input::expand_inputs(&args.inputs)
+        .map_err(|e| CliError::Input(args.inputs.join(" "), e))?;
+    for path in paths {
+        info!(target: "main", "Reading data from {}", path.display());
+        let name = path.display().to_string();
+        let format = args.input_format.unwrap_or(InputFormat::from_path(&path));
+        let reader =
+            compression::open_input(&path).map_err(|e| CliError::Input(name.clone(), e))?;
+        let summary =
+            process(&name, format, reader).map_err(|e| CliError::Flow(name.clone(), e))?;
+        let rejected = summary.rejected > 0;
+        summaries.push(InputSummary {
+            input: name,
+            ..summary
+        });
+        if args.strict && rejected {
+            break;
+        }
+    }
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{client_id, Cli, Command};
+    use clap::{CommandFactory, Parser};
+    use payments::ClientRegistry;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_stats_file() {
+        let cli = Cli::try_parse_from(["payments", "--stats", "stats.json", "day01.csv"]).unwrap();
+        assert_eq!(cli.run.stats.as_deref(), Some("stats.json"));
+        assert_eq!(cli.run.inputs, ["day01.csv"]);
+
+        let cli =
+            Cli::try_parse_from(["payments", "process", "day01.csv", "--stats", "-"]).unwrap();
+        let Some(Command::Process(run)) = cli.command else {
+            panic!("expected the process subcommand");
+        };
+        assert_eq!(run.stats.as_deref(), Some("-"));
+        assert_eq!(run.inputs, ["day01.csv"]);
+
+        assert!(Cli::try_parse_from(["payments", "day01.csv", "--stats"]).is_err());
+    }
+
+    #[test]
+    fn test_client_id() {
+        let mut registry = ClientRegistry::new();
+        registry.intern("acc-1");
+        assert_eq!(client_id("7", None).unwrap(), Some(7));
+        assert!(client_id("acc-1", None).is_err());
+        assert_eq!(client_id("acc-1", Some(&registry)).unwrap(), Some(0));
+        assert_eq!(client_id("7", Some(&registry)).unwrap(), None);
+
+        let flagged = [
+            "payments",
+            "--external-client-ids",
+            "--flagged-output",
+            "f.csv",
+        ];
+        assert!(Cli::try_parse_from(flagged).is_err());
     }
 }
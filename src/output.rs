@@ -0,0 +1,118 @@
+use csv::WriterBuilder;
+use serde::Serialize;
+use std::io::Write;
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    /// Single JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::JsonLines),
+            _ => Err("unexpected output format"),
+        }
+    }
+}
+
+/// Write records in the given format, CSV output has a header line.
+pub fn write_records<T: Serialize, W: Write>(
+    records: Vec<T>,
+    output: W,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    match format {
+        OutputFormat::Csv => write_csv(records, output),
+        OutputFormat::Json => write_json(records, output),
+        OutputFormat::JsonLines => write_json_lines(records, output),
+    }
+}
+
+pub fn write_csv<T: Serialize, W: Write>(records: Vec<T>, output: W) -> std::io::Result<()> {
+    let mut wtr = WriterBuilder::new()
+        .flexible(true)
+        .has_headers(true)
+        .from_writer(output);
+
+    for record in records {
+        wtr.serialize(record)?;
+    }
+    wtr.flush()
+}
+
+fn write_json<T: Serialize, W: Write>(records: Vec<T>, output: W) -> std::io::Result<()> {
+    write_json_record(&records, output)
+}
+
+/// Write a single record as pretty-printed JSON.
+pub fn write_json_record<T: Serialize, W: Write>(record: &T, mut output: W) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut output, record)?;
+    writeln!(output)
+}
+
+fn write_json_lines<T: Serialize, W: Write>(records: Vec<T>, mut output: W) -> std::io::Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut output, &record)?;
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::client::ClientView;
+    use crate::output::{write_records, OutputFormat};
+
+    fn clients() -> Vec<ClientView> {
+        vec![
+            ClientView {
+                client: 1,
+                available: amount!(1.5),
+                held: amount!(0.1234),
+                total: amount!(1.6234),
+                locked: false,
+            },
+            ClientView {
+                client: 2,
+                available: amount!(79228162514264337593543950335),
+                held: amount!(0),
+                total: amount!(79228162514264337593543950335),
+                locked: true,
+            },
+        ]
+    }
+
+    fn write(format: OutputFormat) -> String {
+        let mut output = vec![];
+        write_records(clients(), &mut output, format).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_json_lines() {
+        assert_eq!(
+            write(OutputFormat::JsonLines),
+            r#"{"client":1,"available":"1.5","held":"0.1234","total":"1.6234","locked":false}
+{"client":2,"available":"79228162514264337593543950335","held":"0","total":"79228162514264337593543950335","locked":true}
+"#
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let parsed: Vec<ClientView> = serde_json::from_str(&write(OutputFormat::Json)).unwrap();
+        assert_eq!(parsed, clients());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::types::Amount;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Business rules applied by the engine.
+///
+/// Defaults keep the behavior of the engine without any configuration.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnginePolicy {
+    pub frozen: FrozenPolicy,
+    pub disputes: DisputePolicy,
+    pub validation: ValidationRules,
+    pub unique_ids: IdUniqueness,
+}
+
+/// Handling of transactions of frozen clients.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FrozenPolicy {
+    /// Every transaction is rejected.
+    #[default]
+    RejectAll,
+    /// Deposits are still credited, everything else is rejected.
+    AllowDeposits,
+}
+
+/// Transactions that can be disputed.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputePolicy {
+    #[default]
+    DepositsOnly,
+    /// Disputed withdrawal holds its amount until it is resolved (the withdrawal stands)
+    /// or charged back (the amount is returned to the client).
+    ///
+    /// Held amount is a provisional credit of money that already left the account:
+    /// available funds do not change, so the total grows by the amount while the dispute is open.
+    /// Only applied withdrawals are stored, so a rejected one cannot be disputed.
+    DepositsAndWithdrawals,
+}
+
+/// Checks of transactions before they are applied.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationRules {
+    /// Deposits and withdrawals above this amount are rejected.
+    pub max_amount: Option<Amount>,
+    /// Withdrawals may take available funds below zero.
+    pub allow_overdraft: bool,
+    /// Disputes, resolves and chargebacks of clients without any applied transaction
+    /// are rejected as `unknown_client` rather than `missing_transaction`.
+    pub reject_unknown_clients: bool,
+}
+
+/// Handling of deposits and withdrawals reusing an id.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdUniqueness {
+    /// Later transaction replaces the stored one, unless it is under dispute.
+    #[default]
+    Unchecked,
+    /// Reuse of an id of a stored transaction of the same client is rejected.
+    PerClient,
+}
+
+/// Reason the policy file could not be used.
+#[derive(Debug)]
+pub enum PolicyError {
+    Io(std::io::Error),
+    Parse(String),
+    Invalid(String),
+}
+
+impl EnginePolicy {
+    /// Load and validate policy from a JSON file (`.json` extension) or a TOML file.
+    pub fn from_file(path: &str) -> Result<Self, PolicyError> {
+        let contents = std::fs::read_to_string(path).map_err(PolicyError::Io)?;
+        let policy = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&contents)?,
+            _ => Self::from_toml(&contents)?,
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, PolicyError> {
+        toml::from_str(contents).map_err(|e| PolicyError::Parse(e.to_string()))
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, PolicyError> {
+        serde_json::from_str(contents).map_err(|e| PolicyError::Parse(e.to_string()))
+    }
+
+    /// Check values that parse, but make no sense.
+    pub fn validate(&self) -> Result<(), PolicyError> {
+        if let Some(max_amount) = self.validation.max_amount {
+            if max_amount <= Amount::ZERO {
+                return Err(PolicyError::Invalid(format!(
+                    "validation.max_amount must be positive, got {max_amount}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::Io(e) => write!(f, "unable to read policy file: {e}"),
+            PolicyError::Parse(e) => write!(f, "malformed policy: {e}"),
+            PolicyError::Invalid(e) => write!(f, "invalid policy: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::policy::{
+        DisputePolicy, EnginePolicy, FrozenPolicy, IdUniqueness, PolicyError, ValidationRules,
+    };
+
+    #[test]
+    fn test_policy_file() {
+        let policy = EnginePolicy::from_toml(
+            r#"
+frozen = "allow_deposits"
+unique_ids = "per_client"
+
+[validation]
+max_amount = "1000000"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            policy,
+            EnginePolicy {
+                frozen: FrozenPolicy::AllowDeposits,
+                disputes: DisputePolicy::DepositsOnly,
+                validation: ValidationRules {
+                    max_amount: Some(amount!(1000000)),
+                    allow_overdraft: false,
+                    reject_unknown_clients: false,
+                },
+                unique_ids: IdUniqueness::PerClient,
+            }
+        );
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(EnginePolicy::from_json(&json).unwrap(), policy);
+        assert_eq!(
+            EnginePolicy::from_json("{}").unwrap(),
+            EnginePolicy::default()
+        );
+
+        assert!(matches!(
+            EnginePolicy::from_toml("frozen = \"ignore\""),
+            Err(PolicyError::Parse(_))
+        ));
+        assert!(matches!(
+            EnginePolicy::from_toml("[validation]\nmax_amounts = \"5\""),
+            Err(PolicyError::Parse(_))
+        ));
+        let negative = EnginePolicy::from_toml("[validation]\nmax_amount = \"-5\"").unwrap();
+        assert!(matches!(negative.validate(), Err(PolicyError::Invalid(_))));
+        assert!(matches!(
+            EnginePolicy::from_file("missing.toml"),
+            Err(PolicyError::Io(_))
+        ));
+    }
+}
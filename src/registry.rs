@@ -0,0 +1,177 @@
+use crate::client::ClientView;
+use crate::input::InputError;
+use crate::statement::StatementEntry;
+use crate::transactions::TransactionView;
+use crate::types::ClientID;
+use std::collections::HashMap;
+
+/// Interning table of external client references, e.g. UUIDs or account numbers.
+///
+/// Every reference gets the next `ClientID` on first sight, starting from 0,
+/// so the engine keeps working with numeric ids.
+/// The table only grows, references are kept for the lifetime of the registry.
+#[derive(Debug, Default, Clone)]
+pub struct ClientRegistry {
+    ids: HashMap<String, ClientID>,
+    /// References indexed by their id.
+    references: Vec<String>,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Id of the reference, assigned if it was not seen before.
+    pub fn intern(&mut self, reference: &str) -> ClientID {
+        if let Some(&id) = self.ids.get(reference) {
+            return id;
+        }
+        let id = self.references.len() as ClientID;
+        self.ids.insert(reference.to_string(), id);
+        self.references.push(reference.to_string());
+        id
+    }
+
+    /// Id of a reference seen before.
+    pub fn get(&self, reference: &str) -> Option<ClientID> {
+        self.ids.get(reference).copied()
+    }
+
+    /// Reference the id was assigned to.
+    pub fn reference(&self, id: ClientID) -> Option<&str> {
+        let index = usize::try_from(id).ok()?;
+        self.references.get(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.references.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+
+    /// Row with the client reference replaced by its id.
+    pub fn intern_view(&mut self, view: TransactionView<String>) -> TransactionView {
+        TransactionView {
+            type_str: view.type_str,
+            client: self.intern(&view.client),
+            tx: view.tx,
+            amount: view.amount,
+            timestamp: view.timestamp,
+        }
+    }
+
+    /// Rows with client references replaced by their ids, malformed rows are kept as is.
+    pub fn intern_rows<'a, I>(
+        &'a mut self,
+        rows: I,
+    ) -> impl Iterator<Item = Result<TransactionView, InputError>> + 'a
+    where
+        I: Iterator<Item = Result<TransactionView<String>, InputError>> + 'a,
+    {
+        rows.map(|row| row.map(|view| self.intern_view(view)))
+    }
+
+    /// Client balances with the id replaced by its reference.
+    /// Ids that were not assigned by the registry are written as numbers.
+    pub fn external_client(&self, view: ClientView) -> ClientView<String> {
+        ClientView {
+            client: self.external_id(view.client),
+            available: view.available,
+            held: view.held,
+            total: view.total,
+            locked: view.locked,
+        }
+    }
+
+    /// Statement line with the client id replaced by its reference.
+    pub fn external_entry(&self, entry: StatementEntry) -> StatementEntry<String> {
+        StatementEntry {
+            client: self.external_id(entry.client),
+            index: entry.index,
+            timestamp: entry.timestamp,
+            type_str: entry.type_str,
+            tx: entry.tx,
+            amount: entry.amount,
+            available_change: entry.available_change,
+            held_change: entry.held_change,
+            available: entry.available,
+            held: entry.held,
+            total: entry.total,
+            locked: entry.locked,
+            note: entry.note,
+        }
+    }
+
+    fn external_id(&self, id: ClientID) -> String {
+        match self.reference(id) {
+            Some(reference) => reference.to_string(),
+            None => id.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::client::ClientView;
+    use crate::input::read_csv_as;
+    use crate::registry::ClientRegistry;
+    use crate::transactions::Transaction;
+
+    #[test]
+    fn test_intern() {
+        let mut registry = ClientRegistry::new();
+        assert!(registry.is_empty());
+        let alice = "6f1c2a4e-2b7d-4c4e-9a51-0d3b8e7f1a90";
+        assert_eq!(registry.intern(alice), 0);
+        assert_eq!(registry.intern("bob"), 1);
+        assert_eq!(registry.intern(alice), 0);
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.get("bob"), Some(1));
+        assert_eq!(registry.get("carol"), None);
+        assert_eq!(registry.reference(0), Some(alice));
+        assert_eq!(registry.reference(2), None);
+    }
+
+    #[test]
+    fn test_intern_rows() {
+        let input = "type, client, tx, amount
+deposit, acc-7, 1, 1.0
+deposit, 7, 2, 2.0
+deposit, acc-7, 3,
+withdrawal, 7, 4, 1.0";
+        let mut registry = ClientRegistry::new();
+        let rows: Vec<_> = registry
+            .intern_rows(read_csv_as(input.as_bytes()))
+            .collect();
+        let clients: Vec<_> = rows
+            .into_iter()
+            .map(|row| Transaction::try_from(row.unwrap()).map(|tx| tx.client()))
+            .collect();
+        // References are compared as strings, `7` is not the same client as `acc-7`.
+        assert_eq!(clients[0], Ok(0));
+        assert_eq!(clients[1], Ok(1));
+        assert!(clients[2].is_err());
+        assert_eq!(clients[3], Ok(1));
+
+        let view = ClientView {
+            client: 0,
+            available: amount!(1),
+            held: amount!(0),
+            total: amount!(1),
+            locked: false,
+        };
+        assert_eq!(registry.external_client(view).client, "acc-7");
+        let unknown = ClientView {
+            client: 5,
+            available: amount!(0),
+            held: amount!(0),
+            total: amount!(0),
+            locked: false,
+        };
+        assert_eq!(registry.external_client(unknown).client, "5");
+    }
+}
@@ -0,0 +1,87 @@
+use crate::transactions::Transaction;
+use crate::types::Timestamp;
+use std::collections::BTreeMap;
+
+/// Buffers transactions and releases them in timestamp order.
+///
+/// A transaction is released once a transaction at least `tolerance` seconds
+/// newer has been seen, so input shuffled within the tolerance comes out sorted.
+/// Transactions with the same timestamp keep their input order.
+/// Transactions without a timestamp are ordered as if they had the newest
+/// timestamp seen so far.
+pub struct ReorderBuffer {
+    tolerance: Timestamp,
+    latest: Timestamp,
+    sequence: u64,
+    buffer: BTreeMap<(Timestamp, u64), Transaction>,
+}
+
+impl ReorderBuffer {
+    pub fn new(tolerance: Timestamp) -> Self {
+        Self {
+            tolerance,
+            latest: 0,
+            sequence: 0,
+            buffer: BTreeMap::new(),
+        }
+    }
+
+    /// Add transaction to the buffer and return all transactions that are ready.
+    pub fn push(&mut self, tx: Transaction) -> Vec<Transaction> {
+        let timestamp = tx.timestamp().unwrap_or(self.latest);
+        self.latest = self.latest.max(timestamp);
+        self.buffer.insert((timestamp, self.sequence), tx);
+        self.sequence += 1;
+
+        let mut ready = vec![];
+        while let Some(entry) = self.buffer.first_entry() {
+            if entry.key().0.saturating_add(self.tolerance) > self.latest {
+                break;
+            }
+            ready.push(entry.remove());
+        }
+        ready
+    }
+
+    /// Release all remaining transactions.
+    pub fn flush(&mut self) -> Vec<Transaction> {
+        std::mem::take(&mut self.buffer).into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::reorder::ReorderBuffer;
+    use crate::transactions::{DepositTransaction, Transaction};
+
+    fn deposit(tx: u64, timestamp: Option<u64>) -> Transaction {
+        Transaction::Deposit(DepositTransaction {
+            client: 1,
+            tx,
+            amount: amount!(1.0),
+            timestamp,
+        })
+    }
+
+    #[test]
+    fn test_reorder() {
+        let mut buffer = ReorderBuffer::new(10);
+        assert_eq!(buffer.push(deposit(1, Some(100))), vec![]);
+        assert_eq!(buffer.push(deposit(2, Some(95))), vec![]);
+        assert_eq!(buffer.push(deposit(3, None)), vec![]);
+        assert_eq!(
+            buffer.push(deposit(4, Some(106))),
+            vec![deposit(2, Some(95))]
+        );
+        assert_eq!(
+            buffer.push(deposit(5, Some(120))),
+            vec![
+                deposit(1, Some(100)),
+                deposit(3, None),
+                deposit(4, Some(106))
+            ]
+        );
+        assert_eq!(buffer.flush(), vec![deposit(5, Some(120))]);
+    }
+}
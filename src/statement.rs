@@ -0,0 +1,59 @@
+use crate::history::BalanceRecord;
+use crate::types::{Amount, ClientID, Outcome, Timestamp, TxID};
+use serde::Serialize;
+
+/// Single line of the client statement, `C` is `String` for external client references.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StatementEntry<C = ClientID> {
+    pub client: C,
+    /// 1-based index of the input transaction.
+    pub index: u64,
+    pub timestamp: Option<Timestamp>,
+    #[serde(rename = "type")]
+    pub type_str: &'static str,
+    pub tx: TxID,
+    /// Amount of the transaction, or of the referenced deposit or withdrawal.
+    pub amount: Option<Amount>,
+    pub available_change: Amount,
+    pub held_change: Amount,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+    /// Rejection reason if the transaction was not applied.
+    pub note: Option<String>,
+}
+
+/// Turn client balance history into a statement.
+///
+/// Changes are the difference between consecutive records,
+/// amounts are the ones recorded with the events.
+pub fn build_statement(records: &[BalanceRecord]) -> Vec<StatementEntry> {
+    let mut available = Amount::ZERO;
+    let mut held = Amount::ZERO;
+    let mut statement = Vec::with_capacity(records.len());
+    for record in records {
+        let event = &record.event;
+        statement.push(StatementEntry {
+            client: event.client,
+            index: record.index,
+            timestamp: event.timestamp,
+            type_str: event.type_str,
+            tx: event.tx,
+            amount: record.amount,
+            available_change: record.available.saturating_sub(available),
+            held_change: record.held.saturating_sub(held),
+            available: record.available,
+            held: record.held,
+            total: record.available.saturating_add(record.held),
+            locked: record.locked,
+            note: match event.result {
+                Outcome::Applied => None,
+                Outcome::Rejected(rejection) => Some(rejection.to_string()),
+            },
+        });
+        available = record.available;
+        held = record.held;
+    }
+    statement
+}
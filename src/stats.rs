@@ -0,0 +1,200 @@
+use crate::engine::Engine;
+use crate::flow::InputSummary;
+use crate::policy::EnginePolicy;
+use crate::transactions::Transaction;
+use crate::types::{Amount, EngineError, Outcome};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Total amounts moved by applied transactions, clamped to the range of `Amount`.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Volume {
+    pub deposited: Amount,
+    pub withdrawn: Amount,
+    /// Amounts moved to held funds by disputes, resolves do not decrease it.
+    pub held: Amount,
+    pub charged_back: Amount,
+}
+
+/// Counts and volumes of processed input transactions.
+///
+/// Events generated by the engine itself (e.g. dispute timeouts) are not counted.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct TransactionStats {
+    /// Number of processed transactions of every type.
+    pub transactions: BTreeMap<&'static str, u64>,
+    /// Number of applied transactions and of rejected ones per reason.
+    pub outcomes: BTreeMap<String, u64>,
+    pub volume: Volume,
+    /// Largest number of deposits and withdrawals stored by the engine,
+    /// sampled at the end of every input.
+    pub peak_stored_transactions: u64,
+}
+
+/// Summary of the whole run.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunStats {
+    pub inputs: Vec<InputSummary>,
+    pub rows: u64,
+    pub applied: u64,
+    pub rejected: u64,
+    #[serde(flatten)]
+    pub counts: TransactionStats,
+    /// Number of locked accounts at the end of the run.
+    pub frozen_accounts: u64,
+    pub elapsed_seconds: f64,
+    /// Processed rows per second.
+    pub throughput: f64,
+    /// Business rules the engine applied, so the run can be reproduced.
+    pub policy: EnginePolicy,
+}
+
+impl TransactionStats {
+    /// Count the outcome of a processed transaction.
+    ///
+    /// Amounts of disputes and chargebacks are looked up in the engine.
+    pub fn record(
+        &mut self,
+        engine: &Engine,
+        tx: &Transaction,
+        outcome: Outcome,
+    ) -> Result<(), EngineError> {
+        *self.transactions.entry(tx.type_str()).or_default() += 1;
+        *self.outcomes.entry(outcome.to_string()).or_default() += 1;
+        if outcome != Outcome::Applied {
+            return Ok(());
+        }
+        match tx {
+            Transaction::Deposit(deposit) => {
+                self.volume.deposited = self.volume.deposited.saturating_add(deposit.amount)
+            }
+            Transaction::Withdrawal(withdrawal) => {
+                self.volume.withdrawn = self.volume.withdrawn.saturating_add(withdrawal.amount)
+            }
+            Transaction::Dispute(_) | Transaction::Chargeback(_) => {
+                let Some(amount) = engine
+                    .get_transaction(&tx.uid())?
+                    .and_then(|tx| tx.amount())
+                else {
+                    return Ok(());
+                };
+                match tx {
+                    Transaction::Dispute(_) => {
+                        self.volume.held = self.volume.held.saturating_add(amount)
+                    }
+                    _ => self.volume.charged_back = self.volume.charged_back.saturating_add(amount),
+                }
+            }
+            Transaction::Resolve(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Sample the number of transactions stored by the engine.
+    pub fn sample_storage(&mut self, engine: &Engine) -> Result<(), EngineError> {
+        let stored = engine.count_transactions()? as u64;
+        self.peak_stored_transactions = self.peak_stored_transactions.max(stored);
+        Ok(())
+    }
+
+    /// Add counts of another part of the input to these ones.
+    pub fn merge(&mut self, other: &TransactionStats) {
+        for (type_str, count) in &other.transactions {
+            *self.transactions.entry(type_str).or_default() += count;
+        }
+        for (outcome, count) in &other.outcomes {
+            *self.outcomes.entry(outcome.clone()).or_default() += count;
+        }
+        self.volume.deposited = self.volume.deposited.saturating_add(other.volume.deposited);
+        self.volume.withdrawn = self.volume.withdrawn.saturating_add(other.volume.withdrawn);
+        self.volume.held = self.volume.held.saturating_add(other.volume.held);
+        self.volume.charged_back = self
+            .volume
+            .charged_back
+            .saturating_add(other.volume.charged_back);
+        self.peak_stored_transactions = self
+            .peak_stored_transactions
+            .max(other.peak_stored_transactions);
+    }
+}
+
+impl RunStats {
+    /// Combine summaries of all inputs with the final state of the engine.
+    pub fn new(
+        engine: &Engine,
+        inputs: Vec<InputSummary>,
+        elapsed: Duration,
+    ) -> Result<Self, EngineError> {
+        let mut total = InputSummary::default();
+        for summary in &inputs {
+            total.merge(summary);
+        }
+        let frozen_accounts = engine
+            .get_all_clients()?
+            .iter()
+            .filter(|client| client.locked)
+            .count() as u64;
+        let elapsed_seconds = elapsed.as_secs_f64();
+        Ok(Self {
+            inputs,
+            rows: total.rows,
+            applied: total.applied,
+            rejected: total.rejected,
+            counts: total.stats,
+            frozen_accounts,
+            elapsed_seconds,
+            policy: engine.policy().clone(),
+            throughput: if elapsed_seconds > 0.0 {
+                total.rows as f64 / elapsed_seconds
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::engine::Engine;
+    use crate::flow::{process_csv_transactions, FlowConfig};
+    use crate::stats::RunStats;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_stats() {
+        let engine = Engine::new();
+        let input = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 1, 2, 2.5
+withdrawal, 1, 3, 0.5
+withdrawal, 1, 4, 10.0
+dispute, 1, 2,
+chargeback, 1, 2,
+deposit, 1, 5, 1.0
+deposit, 2, 6, 3.0
+dispute, 2, 6,
+resolve, 2, 6,
+dispute, 2, 7,";
+        let summary =
+            process_csv_transactions(&engine, &FlowConfig::default(), input.as_bytes()).unwrap();
+        let stats = RunStats::new(&engine, vec![summary], Duration::from_secs(2)).unwrap();
+        assert_eq!((stats.rows, stats.applied, stats.rejected), (11, 8, 3));
+        assert_eq!(stats.counts.transactions["deposit"], 4);
+        assert_eq!(stats.counts.transactions["dispute"], 3);
+        assert_eq!(stats.counts.outcomes["applied"], 8);
+        assert_eq!(stats.counts.outcomes["insufficient_funds"], 1);
+        assert_eq!(stats.counts.outcomes["frozen_account"], 1);
+        assert_eq!(stats.counts.outcomes["missing_transaction"], 1);
+        assert_eq!(stats.counts.volume.deposited, amount!(6.5));
+        assert_eq!(stats.counts.volume.withdrawn, amount!(0.5));
+        assert_eq!(stats.counts.volume.held, amount!(5.5));
+        assert_eq!(stats.counts.volume.charged_back, amount!(2.5));
+        // Rejected withdrawal and deposit are not stored.
+        assert_eq!(stats.counts.peak_stored_transactions, 4);
+        assert_eq!(stats.frozen_accounts, 1);
+        assert_eq!(stats.throughput, 5.5);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::async_engine::AsyncEngine;
+use crate::db::AsyncDatabase;
+use crate::input::InputError;
+use crate::transactions::{Transaction, TransactionView};
+use crate::types::ErrorChain;
+use csv::{ReaderBuilder, StringRecord};
+use log::{info, warn};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+};
+use tokio::net::TcpListener;
+
+/// Command that dumps balances of all clients instead of a transaction row.
+const DUMP_COMMAND: &str = "dump";
+
+/// Largest accepted line in bytes, including the line break.
+/// Connection is closed if a line is longer.
+pub const MAX_LINE: usize = 64 * 1024;
+
+/// Format of the rows of a connection, detected from its first line.
+enum LineFormat {
+    /// First line is the header.
+    Csv(StringRecord),
+    JsonLines,
+}
+
+/// Accept connections and handle every one of them on its own task.
+///
+/// Runs until accepting a connection fails.
+pub async fn serve<D: AsyncDatabase + 'static>(engine: Arc<AsyncEngine<D>>, listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let engine = engine.clone();
+                tokio::spawn(async move {
+                    info!(target: "stream", "Connection from {peer}");
+                    let (input, output) = stream.into_split();
+                    if let Err(e) = handle_stream(&engine, input, output).await {
+                        warn!(target: "stream", "Connection from {peer} failed: {e}");
+                    }
+                });
+            }
+            Err(e) => {
+                warn!(target: "stream", "Unable to accept connection: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Apply rows of the input in order and acknowledge every one with a JSON line.
+///
+/// Input is JSON Lines if the first line is a JSON object, otherwise CSV with a header line,
+/// which has to name at least the `type`, `client` and `tx` columns.
+/// A `dump` line is answered with balances of all clients.
+/// Acknowledgements are flushed whenever all received input has been processed.
+/// Engine errors are acknowledged like malformed rows,
+/// a line longer than `MAX_LINE` is acknowledged with an error and ends the connection.
+pub async fn handle_stream<D, R, W>(
+    engine: &AsyncEngine<D>,
+    input: R,
+    output: W,
+) -> std::io::Result<()>
+where
+    D: AsyncDatabase,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(input);
+    let mut writer = BufWriter::new(output);
+    let mut format = None;
+    let mut row = 0;
+    let mut line = vec![];
+
+    loop {
+        line.clear();
+        // One byte more than allowed, to tell a line at the limit from a longer one.
+        let read = (&mut reader)
+            .take(MAX_LINE as u64 + 1)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if read == 0 {
+            break;
+        }
+        if line.len() > MAX_LINE {
+            let error = format!("line is longer than {MAX_LINE} bytes");
+            let response = json!({ "row": row + 1, "error": error });
+            writer.write_all(format!("{response}\n").as_bytes()).await?;
+            writer.flush().await?;
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, error));
+        }
+        let trimmed = std::str::from_utf8(&line)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+            .trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let response = if trimmed.eq_ignore_ascii_case(DUMP_COMMAND) {
+            match engine.get_all_clients().await {
+                Ok(clients) => json!({ "clients": clients }),
+                Err(e) => json!({ "error": ErrorChain(&e).to_string() }),
+            }
+        } else if format.is_none() && !trimmed.starts_with('{') {
+            match parse_header(trimmed) {
+                Ok(headers) => {
+                    format = Some(LineFormat::Csv(headers));
+                    continue;
+                }
+                Err(e) => json!({ "error": e }),
+            }
+        } else {
+            let format = format.get_or_insert(LineFormat::JsonLines);
+            row += 1;
+            match parse_line(format, trimmed) {
+                Ok(tx) => match engine.process_transaction(&tx).await {
+                    Ok(outcome) => json!({ "row": row, "result": outcome }),
+                    Err(e) => json!({ "row": row, "error": ErrorChain(&e).to_string() }),
+                },
+                Err(e) => json!({ "row": row, "error": e }),
+            }
+        };
+        writer.write_all(format!("{response}\n").as_bytes()).await?;
+        if reader.buffer().is_empty() {
+            writer.flush().await?;
+        }
+    }
+    writer.flush().await
+}
+
+/// Parse the CSV header, it has to name the columns every transaction needs.
+fn parse_header(line: &str) -> Result<StringRecord, String> {
+    let headers = read_record(line).map_err(|e| e.to_string())?;
+    match ["type", "client", "tx"]
+        .iter()
+        .all(|column| headers.iter().any(|header| header == *column))
+    {
+        true => Ok(headers),
+        false => Err("expected a CSV header with type, client and tx columns".to_string()),
+    }
+}
+
+fn parse_line(format: &LineFormat, line: &str) -> Result<Transaction, String> {
+    let view: TransactionView = match format {
+        LineFormat::JsonLines => serde_json::from_str(line).map_err(InputError::Json),
+        LineFormat::Csv(headers) => read_record(line)
+            .and_then(|record| record.deserialize(Some(headers)))
+            .map_err(InputError::Csv),
+    }
+    .map_err(|e| ErrorChain(&e).to_string())?;
+    Transaction::try_from(view).map_err(|e| e.to_string())
+}
+
+/// Read a single CSV line with trimmed fields.
+fn read_record(line: &str) -> Result<StringRecord, csv::Error> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_reader(line.as_bytes());
+    let mut record = StringRecord::new();
+    reader.read_record(&mut record)?;
+    Ok(record)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::amount;
+    use crate::client::Client;
+    use crate::db::{AsyncDatabase, InMemoryDB};
+    use crate::engine::Engine;
+    use crate::stream::{handle_stream, serve, MAX_LINE};
+    use crate::transactions::Transaction;
+    use crate::types::{ClientID, DatabaseError, TxUID};
+    use serde_json::{json, Value};
+    use std::future::{ready, Future};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Database that fails every operation.
+    struct BrokenDB;
+
+    impl AsyncDatabase for BrokenDB {
+        fn get_tx(
+            &self,
+            _: &TxUID,
+        ) -> impl Future<Output = Result<Option<Transaction>, DatabaseError>> + Send {
+            ready(Err(DatabaseError::PoisonLock))
+        }
+
+        fn write_tx(
+            &self,
+            _: Transaction,
+        ) -> impl Future<Output = Result<(), DatabaseError>> + Send {
+            ready(Err(DatabaseError::PoisonLock))
+        }
+
+        fn get_client(
+            &self,
+            _: &ClientID,
+        ) -> impl Future<Output = Result<Option<Client>, DatabaseError>> + Send {
+            ready(Err(DatabaseError::PoisonLock))
+        }
+
+        fn write_client(
+            &self,
+            _: ClientID,
+            _: Client,
+        ) -> impl Future<Output = Result<(), DatabaseError>> + Send {
+            ready(Err(DatabaseError::PoisonLock))
+        }
+
+        fn all_clients(
+            &self,
+        ) -> impl Future<Output = Result<Vec<(ClientID, Client)>, DatabaseError>> + Send {
+            ready(Err(DatabaseError::PoisonLock))
+        }
+    }
+
+    fn read_acks(output: &[u8]) -> Vec<Value> {
+        std::str::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let engine = Engine::builder().build_async(InMemoryDB::new());
+        let mut output = vec![];
+        let input = "\
+type, client, tx, amount
+deposit, 1, 1, 2.0
+
+withdrawal, 1, 2, 3.0
+deposit, x, 3, 1.0
+dump
+";
+        handle_stream(&engine, input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+        let acks = read_acks(&output);
+        assert_eq!(acks[0], json!({ "row": 1, "result": "applied" }));
+        assert_eq!(acks[1], json!({ "row": 2, "result": "insufficient_funds" }));
+        assert_eq!(acks[2]["row"], json!(3));
+        assert!(acks[2]["error"].is_string());
+        assert_eq!(acks[3]["clients"][0]["available"], json!("2"));
+
+        let mut output = vec![];
+        let input = r#"{"type": "withdrawal", "client": 1, "tx": 4, "amount": "0.5"}"#;
+        handle_stream(&engine, input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+        assert_eq!(
+            read_acks(&output),
+            vec![json!({ "row": 1, "result": "applied" })]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_errors() {
+        // CSV rows before a valid header are rejected, not taken for the header.
+        let engine = Engine::builder().build_async(InMemoryDB::new());
+        let mut output = vec![];
+        let input = "deposit, 1, 1, 2.0\ntype, client, tx, amount\ndeposit, 1, 2, 2.0\n";
+        handle_stream(&engine, input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+        let acks = read_acks(&output);
+        assert_eq!(acks.len(), 2);
+        assert!(acks[0]["error"].is_string());
+        assert_eq!(acks[0].get("row"), None);
+        assert_eq!(acks[1], json!({ "row": 1, "result": "applied" }));
+
+        // Connection is closed at the first line over the limit.
+        let mut output = vec![];
+        let input = format!(
+            "type, client, tx, amount\ndeposit, 1, 3, 1.{}\ndeposit, 1, 4, 1.0\n",
+            "0".repeat(MAX_LINE)
+        );
+        let result = handle_stream(&engine, input.as_bytes(), &mut output).await;
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        let acks = read_acks(&output);
+        assert_eq!(acks.len(), 1);
+        assert_eq!(acks[0]["row"], json!(1));
+        assert!(acks[0]["error"].is_string());
+        assert_eq!(engine.get_client(&1).await.unwrap().available, amount!(2.0));
+
+        // Engine errors are acknowledged and the connection goes on.
+        let engine = Engine::builder().build_async(BrokenDB);
+        let mut output = vec![];
+        let input = "type, client, tx, amount\ndeposit, 1, 1, 2.0\ndump\n";
+        handle_stream(&engine, input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+        let acks = read_acks(&output);
+        assert_eq!(acks.len(), 2);
+        assert_eq!(acks[0]["row"], json!(1));
+        assert!(acks[0]["error"].is_string());
+        assert!(acks[1]["error"].is_string());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let engine = Arc::new(Engine::builder().build_async(InMemoryDB::new()));
+        tokio::spawn(serve(engine, listener));
+
+        let connections: Vec<_> = (0..16)
+            .map(|client| {
+                tokio::spawn(async move {
+                    let mut stream = TcpStream::connect(addr).await.unwrap();
+                    let mut input = "type, client, tx, amount\n".to_string();
+                    for tx in 0..50u64 {
+                        input += &format!("deposit, {client}, {tx}, 1.0\n");
+                    }
+                    input += &format!("withdrawal, {client}, 50, 50.0\n");
+                    stream.write_all(input.as_bytes()).await.unwrap();
+                    stream.shutdown().await.unwrap();
+                    let mut output = vec![];
+                    stream.read_to_end(&mut output).await.unwrap();
+                    let acks = read_acks(&output);
+                    assert_eq!(acks.len(), 51);
+                    assert_eq!(acks[50], json!({ "row": 51, "result": "applied" }));
+                })
+            })
+            .collect();
+        for connection in connections {
+            connection.await.unwrap();
+        }
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"dump\n").await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut output = vec![];
+        stream.read_to_end(&mut output).await.unwrap();
+        let dump = &read_acks(&output)[0];
+        let clients = dump["clients"].as_array().unwrap();
+        assert_eq!(clients.len(), 16);
+        assert!(clients.iter().all(|client| client["total"] == json!("0")));
+    }
+}
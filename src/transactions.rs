@@ -1,13 +1,18 @@
-use crate::types::{Amount, ClientID, TxID, TxUID};
+use crate::db::TransactionRecord;
+use crate::types::{Amount, ClientID, Timestamp, TxID, TxUID};
 use serde::{Deserialize, Serialize};
 
+/// Input row, `C` is `String` for rows with external client references.
 #[derive(Serialize, Deserialize)]
-pub struct TransactionView {
+pub struct TransactionView<C = ClientID> {
     #[serde(rename = "type")]
     pub type_str: String,
-    pub client: ClientID,
+    pub client: C,
     pub tx: TxID,
     pub amount: Option<Amount>,
+    /// Optional column, transactions without it are not checked for ordering.
+    #[serde(default)]
+    pub timestamp: Option<Timestamp>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -19,8 +24,43 @@ pub enum Transaction {
     Chargeback(ChargebackTransaction),
 }
 
+impl From<&Transaction> for TransactionView {
+    fn from(tx: &Transaction) -> Self {
+        Self {
+            type_str: tx.type_str().to_string(),
+            client: tx.client(),
+            tx: tx.id(),
+            amount: tx.amount(),
+            timestamp: tx.timestamp(),
+        }
+    }
+}
+
+/// Reason a parsed row is not a valid transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    /// Deposit or withdrawal without an amount, holds the transaction type.
+    MissingAmount(&'static str),
+    UnexpectedType(String),
+}
+
+impl std::fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionError::MissingAmount(type_str) => {
+                write!(f, "no amount provided for the {type_str} transaction")
+            }
+            TransactionError::UnexpectedType(type_str) => {
+                write!(f, "unexpected transaction type `{type_str}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
 impl TryFrom<TransactionView> for Transaction {
-    type Error = &'static str;
+    type Error = TransactionError;
 
     fn try_from(tx: TransactionView) -> Result<Self, Self::Error> {
         match tx.type_str.as_str() {
@@ -29,35 +69,95 @@ impl TryFrom<TransactionView> for Transaction {
                 tx: tx.tx,
                 amount: tx
                     .amount
-                    .expect("No amount provided for the Deposit transaction."),
+                    .ok_or(TransactionError::MissingAmount("deposit"))?,
+                timestamp: tx.timestamp,
             })),
             "withdrawal" => Ok(Transaction::Withdrawal(WithdrawalTransaction {
                 client: tx.client,
                 tx: tx.tx,
                 amount: tx
                     .amount
-                    .expect("No amount provided for the Withdrawal transaction."),
+                    .ok_or(TransactionError::MissingAmount("withdrawal"))?,
+                timestamp: tx.timestamp,
             })),
             "dispute" => Ok(Transaction::Dispute(DisputeTransaction {
                 client: tx.client,
                 tx: tx.tx,
+                timestamp: tx.timestamp,
             })),
 
             "resolve" => Ok(Transaction::Resolve(ResolveTransaction {
                 client: tx.client,
                 tx: tx.tx,
+                timestamp: tx.timestamp,
             })),
 
             "chargeback" => Ok(Transaction::Chargeback(ChargebackTransaction {
                 client: tx.client,
                 tx: tx.tx,
+                timestamp: tx.timestamp,
             })),
-            _ => Err("unexpected transaction type"),
+            _ => Err(TransactionError::UnexpectedType(tx.type_str)),
         }
     }
 }
 
 impl Transaction {
+    pub fn deposit(client: ClientID, tx: TxID, amount: Amount) -> Self {
+        Transaction::Deposit(DepositTransaction {
+            client,
+            tx,
+            amount,
+            timestamp: None,
+        })
+    }
+
+    pub fn withdrawal(client: ClientID, tx: TxID, amount: Amount) -> Self {
+        Transaction::Withdrawal(WithdrawalTransaction {
+            client,
+            tx,
+            amount,
+            timestamp: None,
+        })
+    }
+
+    /// Dispute of the deposit `tx` of the client.
+    pub fn dispute(client: ClientID, tx: TxID) -> Self {
+        Transaction::Dispute(DisputeTransaction {
+            client,
+            tx,
+            timestamp: None,
+        })
+    }
+
+    pub fn resolve(client: ClientID, tx: TxID) -> Self {
+        Transaction::Resolve(ResolveTransaction {
+            client,
+            tx,
+            timestamp: None,
+        })
+    }
+
+    pub fn chargeback(client: ClientID, tx: TxID) -> Self {
+        Transaction::Chargeback(ChargebackTransaction {
+            client,
+            tx,
+            timestamp: None,
+        })
+    }
+
+    /// Same transaction happening at `timestamp`.
+    pub fn at(mut self, timestamp: Timestamp) -> Self {
+        match &mut self {
+            Transaction::Deposit(tx) => tx.timestamp = Some(timestamp),
+            Transaction::Withdrawal(tx) => tx.timestamp = Some(timestamp),
+            Transaction::Dispute(tx) => tx.timestamp = Some(timestamp),
+            Transaction::Resolve(tx) => tx.timestamp = Some(timestamp),
+            Transaction::Chargeback(tx) => tx.timestamp = Some(timestamp),
+        }
+        self
+    }
+
     pub fn client(&self) -> ClientID {
         match self {
             Transaction::Deposit(tx) => tx.client,
@@ -78,6 +178,35 @@ impl Transaction {
         }
     }
 
+    pub fn type_str(&self) -> &'static str {
+        match self {
+            Transaction::Deposit(_) => "deposit",
+            Transaction::Withdrawal(_) => "withdrawal",
+            Transaction::Dispute(_) => "dispute",
+            Transaction::Resolve(_) => "resolve",
+            Transaction::Chargeback(_) => "chargeback",
+        }
+    }
+
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Transaction::Deposit(tx) => tx.timestamp,
+            Transaction::Withdrawal(tx) => tx.timestamp,
+            Transaction::Dispute(tx) => tx.timestamp,
+            Transaction::Resolve(tx) => tx.timestamp,
+            Transaction::Chargeback(tx) => tx.timestamp,
+        }
+    }
+
+    /// Amount of a deposit or withdrawal.
+    pub fn amount(&self) -> Option<Amount> {
+        match self {
+            Transaction::Deposit(tx) => Some(tx.amount),
+            Transaction::Withdrawal(tx) => Some(tx.amount),
+            _ => None,
+        }
+    }
+
     pub fn uid(&self) -> TxUID {
         (self.client(), self.id())
     }
@@ -87,11 +216,18 @@ impl Transaction {
     }
 }
 
+impl TransactionRecord for Transaction {
+    fn uid(&self) -> TxUID {
+        Transaction::uid(self)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct DepositTransaction {
     pub client: ClientID,
     pub tx: TxID,
     pub amount: Amount,
+    pub timestamp: Option<Timestamp>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -99,22 +235,26 @@ pub struct WithdrawalTransaction {
     pub client: ClientID,
     pub tx: TxID,
     pub amount: Amount,
+    pub timestamp: Option<Timestamp>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct DisputeTransaction {
     pub client: ClientID,
     pub tx: TxID,
+    pub timestamp: Option<Timestamp>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ResolveTransaction {
     pub client: ClientID,
     pub tx: TxID,
+    pub timestamp: Option<Timestamp>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ChargebackTransaction {
     pub client: ClientID,
     pub tx: TxID,
+    pub timestamp: Option<Timestamp>,
 }
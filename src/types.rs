@@ -1,7 +1,45 @@
-pub type ClientID = u16;
+/// Numeric client id, external references are mapped to it by `ClientRegistry`.
+pub type ClientID = u64;
 pub type TxID = u64;
 pub type TxUID = (ClientID, TxID);
-pub type Amount = rust_decimal::Decimal;
+pub use crate::amount::Amount;
+/// Seconds since the Unix epoch.
+pub type Timestamp = u64;
+
+/// Index of the stripe of `stripes` (locks, workers) the client belongs to.
+pub fn client_stripe(id: ClientID, stripes: usize) -> usize {
+    (id % stripes as ClientID) as usize
+}
+
+/// Result of processing a single transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Applied,
+    Rejected(Rejection),
+}
+
+/// Reason for the transaction to be ignored by the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rejection {
+    FrozenAccount,
+    InsufficientFunds,
+    WithdrawalsRestricted,
+    MissingTransaction,
+    NotADeposit,
+    NotDisputed,
+    /// Transaction is disputed already.
+    AlreadyDisputed,
+    OutOfOrder,
+    DisputeExpired,
+    /// Amount is negative, above the configured maximum or above `Amount::MAX_TRANSACTION`.
+    InvalidAmount,
+    /// Id of a stored transaction of the client is reused.
+    DuplicateTransaction,
+    /// Client has no applied transactions.
+    UnknownClient,
+    /// Resulting balance would not fit into an `Amount`.
+    BalanceOverflow,
+}
 
 #[derive(Debug)]
 pub enum DatabaseError {
@@ -17,6 +55,7 @@ pub enum ClientError {
 pub enum EngineError {
     ClientError(ClientError),
     DatabaseError(DatabaseError),
+    PoisonLock,
 }
 
 impl From<DatabaseError> for ClientError {
@@ -36,3 +75,123 @@ impl From<DatabaseError> for EngineError {
         EngineError::DatabaseError(e)
     }
 }
+
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::PoisonLock => f.write_str("database lock is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::DatabaseError(_) => f.write_str("client storage failed"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::DatabaseError(e) => Some(e),
+        }
+    }
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::ClientError(_) => f.write_str("unable to update client"),
+            EngineError::DatabaseError(_) => f.write_str("engine storage failed"),
+            EngineError::PoisonLock => f.write_str("engine lock is poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EngineError::ClientError(e) => Some(e),
+            EngineError::DatabaseError(e) => Some(e),
+            EngineError::PoisonLock => None,
+        }
+    }
+}
+
+/// Error followed by all its sources, separated by `: `, as reported to users.
+///
+/// Errors of the crate only describe their own context and return the inner error
+/// from `source`, so every message is printed once.
+pub struct ErrorChain<'a>(pub &'a dyn std::error::Error);
+
+impl std::fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(e) = source {
+            write!(f, ": {e}")?;
+            source = e.source();
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Applied => f.write_str("applied"),
+            Outcome::Rejected(rejection) => rejection.fmt(f),
+        }
+    }
+}
+
+impl serde::Serialize for Outcome {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Rejection::FrozenAccount => "frozen_account",
+            Rejection::InsufficientFunds => "insufficient_funds",
+            Rejection::WithdrawalsRestricted => "withdrawals_restricted",
+            Rejection::MissingTransaction => "missing_transaction",
+            Rejection::NotADeposit => "not_a_deposit",
+            Rejection::NotDisputed => "not_disputed",
+            Rejection::AlreadyDisputed => "already_disputed",
+            Rejection::OutOfOrder => "out_of_order",
+            Rejection::DisputeExpired => "dispute_expired",
+            Rejection::InvalidAmount => "invalid_amount",
+            Rejection::DuplicateTransaction => "duplicate_transaction",
+            Rejection::UnknownClient => "unknown_client",
+            Rejection::BalanceOverflow => "balance_overflow",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::{ClientError, DatabaseError, EngineError, ErrorChain};
+    use std::error::Error;
+
+    #[test]
+    fn test_error_source() {
+        let error = EngineError::ClientError(ClientError::DatabaseError(DatabaseError::PoisonLock));
+        assert_eq!(error.to_string(), "unable to update client");
+        let client = error.source().unwrap();
+        assert_eq!(client.to_string(), "client storage failed");
+        let database = client.source().unwrap();
+        assert_eq!(database.to_string(), "database lock is poisoned");
+        assert!(database.source().is_none());
+        assert_eq!(
+            ErrorChain(&error).to_string(),
+            "unable to update client: client storage failed: database lock is poisoned"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use crate::engine::Engine;
+use crate::input::InputError;
+use crate::transactions::{Transaction, TransactionView};
+use crate::types::{ClientID, EngineError, ErrorChain, Outcome, Rejection, TxID, TxUID};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Kind of problem found in an input row, at most one is reported per row.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// Row could not be parsed into a transaction.
+    Malformed,
+    /// Deposit or withdrawal reuses the id of an earlier one of the same client.
+    DuplicateId,
+    /// Dispute, resolve or chargeback refers to a transaction not seen before.
+    DanglingReference,
+    /// Engine would reject the transaction.
+    Rejected,
+}
+
+/// Structure to output validation issues in a specific format.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub input: String,
+    /// 1-based index of the row within the input, header is not counted.
+    pub row: u64,
+    #[serde(rename = "type")]
+    pub type_str: Option<String>,
+    pub client: Option<ClientID>,
+    pub tx: Option<TxID>,
+    pub issue: IssueKind,
+    pub detail: String,
+}
+
+/// Checks input rows and runs them against a throwaway engine.
+///
+/// Rows are checked in input order, without reordering by timestamp.
+pub struct Validator {
+    engine: Engine,
+    /// Ids of all parsed deposits and withdrawals.
+    recorded: HashSet<TxUID>,
+    issues: Vec<ValidationIssue>,
+}
+
+impl Validator {
+    pub fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            recorded: HashSet::new(),
+            issues: vec![],
+        }
+    }
+
+    /// Check a single row, returns the engine outcome if the row could be processed.
+    pub fn check(
+        &mut self,
+        input: &str,
+        row: u64,
+        record: Result<TransactionView, InputError>,
+    ) -> Result<Option<Outcome>, EngineError> {
+        let view = match record {
+            Ok(view) => view,
+            Err(e) => {
+                self.report(
+                    input,
+                    row,
+                    None,
+                    IssueKind::Malformed,
+                    ErrorChain(&e).to_string(),
+                );
+                return Ok(None);
+            }
+        };
+        let (type_str, client, tx) = (view.type_str.clone(), view.client, view.tx);
+        let transaction = match Transaction::try_from(view) {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                let view = Some((type_str, client, tx));
+                self.report(input, row, view, IssueKind::Malformed, e.to_string());
+                return Ok(None);
+            }
+        };
+
+        let uid = transaction.uid();
+        let issue = if transaction.is_recorded() && !self.recorded.insert(uid) {
+            let detail = "transaction id is already used by the client".to_string();
+            Some((IssueKind::DuplicateId, detail))
+        } else if !transaction.is_recorded() && !self.recorded.contains(&uid) {
+            let detail = Rejection::MissingTransaction.to_string();
+            Some((IssueKind::DanglingReference, detail))
+        } else {
+            None
+        };
+        let outcome = self.engine.process_transaction(&transaction)?;
+        let issue = issue.or(match outcome {
+            Outcome::Applied => None,
+            Outcome::Rejected(rejection) => Some((IssueKind::Rejected, rejection.to_string())),
+        });
+        if let Some((issue, detail)) = issue {
+            self.report(input, row, Some((type_str, client, tx)), issue, detail);
+        }
+        Ok(Some(outcome))
+    }
+
+    /// Consume the validator, dropping its engine, and return all issues found.
+    pub fn into_issues(self) -> Vec<ValidationIssue> {
+        self.issues
+    }
+
+    fn report(
+        &mut self,
+        input: &str,
+        row: u64,
+        view: Option<(String, ClientID, TxID)>,
+        issue: IssueKind,
+        detail: String,
+    ) {
+        let (type_str, client, tx) = match view {
+            Some((type_str, client, tx)) => (Some(type_str), Some(client), Some(tx)),
+            None => (None, None, None),
+        };
+        self.issues.push(ValidationIssue {
+            input: input.to_string(),
+            row,
+            type_str,
+            client,
+            tx,
+            issue,
+            detail,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::Engine;
+    use crate::input::read_csv;
+    use crate::validate::{IssueKind, Validator};
+
+    #[test]
+    fn test_validate() {
+        let input = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 1, 1, 2.0
+withdrawal, 1, 2,
+transfer, 1, 3, 1.0
+deposit, x, 4, 1.0
+dispute, 1, 5,
+withdrawal, 2, 6, 1.0
+dispute, 1, 1,
+resolve, 1, 1,";
+        let mut validator = Validator::new(Engine::new());
+        for (row, record) in read_csv(input.as_bytes()).enumerate() {
+            validator.check("-", row as u64 + 1, record).unwrap();
+        }
+        let issues: Vec<_> = validator
+            .into_issues()
+            .into_iter()
+            .map(|issue| (issue.row, issue.issue, issue.detail))
+            .collect();
+        assert_eq!(issues.len(), 6);
+        assert_eq!(issues[0].0, 2);
+        assert_eq!(issues[0].1, IssueKind::DuplicateId);
+        assert_eq!(
+            issues[1],
+            (
+                3,
+                IssueKind::Malformed,
+                "no amount provided for the withdrawal transaction".to_string()
+            )
+        );
+        assert_eq!(
+            issues[2],
+            (
+                4,
+                IssueKind::Malformed,
+                "unexpected transaction type `transfer`".to_string()
+            )
+        );
+        assert_eq!((issues[3].0, issues[3].1), (5, IssueKind::Malformed));
+        assert_eq!(
+            issues[4],
+            (
+                6,
+                IssueKind::DanglingReference,
+                "missing_transaction".to_string()
+            )
+        );
+        assert_eq!(
+            issues[5],
+            (7, IssueKind::Rejected, "insufficient_funds".to_string())
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Checks of the public API of the library.
+use payments::flow::process_transactions;
+use payments::input::read_csv_as;
+use payments::{
+    amount, process_csv_transactions, AsyncDatabase, Client, ClientID, ClientRegistry, ClientView,
+    DatabaseError, Engine, FlowConfig, InMemoryDB, Outcome, Rejection, Transaction, TxUID,
+};
+
+fn client_view(engine: &Engine, id: ClientID) -> ClientView {
+    engine.get_client_view(&id).unwrap().unwrap()
+}
+
+#[test]
+fn test_transactions() {
+    let engine = Engine::new();
+    let transactions = [
+        (Transaction::deposit(1, 1, amount!(10.0)), Outcome::Applied),
+        (Transaction::deposit(1, 2, amount!(5.0)), Outcome::Applied),
+        (
+            Transaction::withdrawal(1, 3, amount!(20.0)),
+            Outcome::Rejected(Rejection::InsufficientFunds),
+        ),
+        (
+            Transaction::withdrawal(1, 4, amount!(3.0)),
+            Outcome::Applied,
+        ),
+        (Transaction::dispute(1, 1), Outcome::Applied),
+        (Transaction::resolve(1, 1), Outcome::Applied),
+        (
+            Transaction::resolve(1, 1),
+            Outcome::Rejected(Rejection::NotDisputed),
+        ),
+        (Transaction::dispute(1, 2), Outcome::Applied),
+        (Transaction::chargeback(1, 2), Outcome::Applied),
+        (
+            Transaction::deposit(1, 5, amount!(1.0)),
+            Outcome::Rejected(Rejection::FrozenAccount),
+        ),
+        (
+            Transaction::dispute(2, 1),
+            Outcome::Rejected(Rejection::MissingTransaction),
+        ),
+    ];
+    for (tx, outcome) in transactions {
+        assert_eq!(engine.process_transaction(&tx).unwrap(), outcome, "{tx:?}");
+    }
+    assert_eq!(
+        client_view(&engine, 1),
+        ClientView {
+            client: 1,
+            available: amount!(7.0),
+            held: amount!(0.0),
+            total: amount!(7.0),
+            locked: true,
+        }
+    );
+}
+
+#[test]
+fn test_timestamps() {
+    let engine = Engine::builder().with_dispute_window(100).build();
+    let transactions = [
+        Transaction::deposit(1, 1, amount!(10.0)).at(1000),
+        Transaction::deposit(1, 2, amount!(10.0)).at(1050),
+        Transaction::dispute(1, 1).at(1200),
+        Transaction::dispute(1, 2).at(1100),
+    ];
+    let outcomes: Vec<_> = transactions
+        .iter()
+        .map(|tx| engine.process_transaction(tx).unwrap())
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            Outcome::Applied,
+            Outcome::Applied,
+            Outcome::Rejected(Rejection::DisputeExpired),
+            Outcome::Rejected(Rejection::OutOfOrder),
+        ]
+    );
+}
+
+#[test]
+fn test_process_csv() {
+    let engine = Engine::new();
+    let input = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 2, 2, 2.0
+withdrawal, 1, 3, 1.5
+dispute, 2, 2,
+";
+    let summary =
+        process_csv_transactions(&engine, &FlowConfig::default(), input.as_bytes()).unwrap();
+    assert_eq!((summary.rows, summary.applied, summary.rejected), (4, 3, 1));
+    assert_eq!(client_view(&engine, 1).available, amount!(1.0));
+    assert_eq!(client_view(&engine, 2).held, amount!(2.0));
+}
+
+#[test]
+fn test_external_client_ids() {
+    let engine = Engine::new();
+    let input = "\
+type, client, tx, amount
+deposit, 1b4e28ba-2fa1-11d2-883f-0016d3cca427, 1, 1.0
+deposit, ACC-0042, 1, 2.0
+withdrawal, 1b4e28ba-2fa1-11d2-883f-0016d3cca427, 2, 0.5
+";
+    let mut registry = ClientRegistry::new();
+    let rows = registry.intern_rows(read_csv_as(input.as_bytes()));
+    let summary = process_transactions(&engine, &FlowConfig::default(), rows).unwrap();
+    assert_eq!((summary.rows, summary.applied, summary.rejected), (3, 3, 0));
+
+    let mut clients: Vec<_> = engine
+        .get_all_clients()
+        .unwrap()
+        .into_iter()
+        .map(|view| registry.external_client(view))
+        .map(|view| (view.client, view.available))
+        .collect();
+    clients.sort();
+    assert_eq!(
+        clients,
+        [
+            (
+                "1b4e28ba-2fa1-11d2-883f-0016d3cca427".to_string(),
+                amount!(0.5)
+            ),
+            ("ACC-0042".to_string(), amount!(2.0)),
+        ]
+    );
+}
+
+#[test]
+fn test_rejected_rows_create_no_clients() {
+    let engine = Engine::new();
+    let input = "\
+type, client, tx, amount
+deposit, 1, 1, 1.0
+dispute, 999, 1,
+chargeback, 998, 1,
+withdrawal, 997, 2, 5.0
+";
+    let summary =
+        process_csv_transactions(&engine, &FlowConfig::default(), input.as_bytes()).unwrap();
+    assert_eq!((summary.applied, summary.rejected), (1, 3));
+    let clients: Vec<ClientID> = engine
+        .get_all_clients()
+        .unwrap()
+        .iter()
+        .map(|client| client.client)
+        .collect();
+    assert_eq!(clients, vec![1]);
+}
+
+/// Stand-in for a backend doing I/O: yields to the executor before every operation.
+struct YieldingDB(InMemoryDB);
+
+impl AsyncDatabase for YieldingDB {
+    async fn get_tx(&self, id: &TxUID) -> Result<Option<Transaction>, DatabaseError> {
+        tokio::task::yield_now().await;
+        self.0.get_tx(id).await
+    }
+
+    async fn write_tx(&self, tx: Transaction) -> Result<(), DatabaseError> {
+        tokio::task::yield_now().await;
+        self.0.write_tx(tx).await
+    }
+
+    async fn get_client(&self, id: &ClientID) -> Result<Option<Client>, DatabaseError> {
+        tokio::task::yield_now().await;
+        self.0.get_client(id).await
+    }
+
+    async fn write_client(&self, id: ClientID, client: Client) -> Result<(), DatabaseError> {
+        tokio::task::yield_now().await;
+        self.0.write_client(id, client).await
+    }
+
+    async fn all_clients(&self) -> Result<Vec<(ClientID, Client)>, DatabaseError> {
+        tokio::task::yield_now().await;
+        self.0.all_clients().await
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_async_engine() {
+    let engine = std::sync::Arc::new(Engine::builder().build_async(YieldingDB(InMemoryDB::new())));
+    let tasks: Vec<_> = (0..8)
+        .map(|client| {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                for tx in 0..20 {
+                    let deposit = Transaction::deposit(client, tx, amount!(1.0));
+                    engine.process_transaction(&deposit).await.unwrap();
+                }
+                let withdrawal = Transaction::withdrawal(client, 20, amount!(5.0));
+                engine.process_transaction(&withdrawal).await.unwrap()
+            })
+        })
+        .collect();
+    for task in tasks {
+        assert_eq!(task.await.unwrap(), Outcome::Applied);
+    }
+    let clients = engine.get_all_clients().await.unwrap();
+    assert_eq!(clients.len(), 8);
+    assert!(clients.iter().all(|client| client.total == amount!(15.0)));
+}
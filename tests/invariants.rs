@@ -0,0 +1,194 @@
+//! Properties of the engine over random transaction sequences.
+use payments::{
+    Amount, Client, ClientID, DisputePolicy, Engine, EnginePolicy, IdUniqueness, Outcome,
+    Transaction, TxID, ValidationRules,
+};
+use proptest::prelude::*;
+use proptest::sample::Index;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, VecDeque};
+
+/// Transaction of a client, disputes, resolves and chargebacks pick one of its
+/// earlier deposits and withdrawals, or the id of the next one, which is missing.
+/// Deposits and withdrawals with an index reuse the id of an earlier one.
+#[derive(Debug, Clone)]
+enum Op {
+    Deposit(Amount, Option<Index>),
+    Withdrawal(Amount, Option<Index>),
+    Dispute(Index),
+    Resolve(Index),
+    Chargeback(Index),
+}
+
+/// Mostly small amounts with up to 4 decimal places, sometimes the largest accepted one.
+fn any_amount() -> impl Strategy<Value = Amount> {
+    prop_oneof![
+        9 => (0..10_000_000i64, 0..=4u32).prop_map(|(m, scale)| Amount::new(Decimal::new(m, scale))),
+        1 => Just(Amount::MAX_TRANSACTION),
+    ]
+}
+
+/// Mostly new ids, sometimes an earlier one.
+fn any_reuse() -> impl Strategy<Value = Option<Index>> {
+    prop::option::weighted(0.2, any::<Index>())
+}
+
+fn any_op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (any_amount(), any_reuse()).prop_map(|(amount, reuse)| Op::Deposit(amount, reuse)),
+        2 => (any_amount(), any_reuse()).prop_map(|(amount, reuse)| Op::Withdrawal(amount, reuse)),
+        2 => any::<Index>().prop_map(Op::Dispute),
+        1 => any::<Index>().prop_map(Op::Resolve),
+        1 => any::<Index>().prop_map(Op::Chargeback),
+    ]
+}
+
+/// Default policy, optionally with disputes of withdrawals, overdrafts and unique ids.
+/// Frozen clients reject everything under all of them.
+fn any_policy() -> impl Strategy<Value = EnginePolicy> {
+    (any::<bool>(), any::<bool>(), any::<bool>()).prop_map(
+        |(withdrawals, allow_overdraft, unique)| EnginePolicy {
+            disputes: match withdrawals {
+                false => DisputePolicy::DepositsOnly,
+                true => DisputePolicy::DepositsAndWithdrawals,
+            },
+            validation: ValidationRules {
+                allow_overdraft,
+                ..Default::default()
+            },
+            unique_ids: match unique {
+                false => IdUniqueness::Unchecked,
+                true => IdUniqueness::PerClient,
+            },
+            ..Default::default()
+        },
+    )
+}
+
+/// Turn operations into transactions, ids of deposits and withdrawals are sequential per client
+/// unless they reuse an earlier one.
+fn build_transactions(ops: Vec<(ClientID, Op)>) -> Vec<Transaction> {
+    let mut recorded: HashMap<ClientID, TxID> = HashMap::new();
+    ops.into_iter()
+        .map(|(client, op)| {
+            let count = recorded.entry(client).or_default();
+            let reference = |index: Index| index.index(*count as usize + 1) as TxID + 1;
+            match op {
+                Op::Deposit(amount, reuse) => {
+                    Transaction::deposit(client, next_id(count, reuse), amount)
+                }
+                Op::Withdrawal(amount, reuse) => {
+                    Transaction::withdrawal(client, next_id(count, reuse), amount)
+                }
+                Op::Dispute(index) => Transaction::dispute(client, reference(index)),
+                Op::Resolve(index) => Transaction::resolve(client, reference(index)),
+                Op::Chargeback(index) => Transaction::chargeback(client, reference(index)),
+            }
+        })
+        .collect()
+}
+
+/// Id of a deposit or withdrawal, a new one unless an earlier one is reused.
+fn next_id(count: &mut TxID, reuse: Option<Index>) -> TxID {
+    match reuse {
+        Some(index) if *count > 0 => index.index(*count as usize) as TxID + 1,
+        _ => {
+            *count += 1;
+            *count
+        }
+    }
+}
+
+/// Check invariants of the client of `tx` right after it was processed,
+/// `before` is the state of the client before it.
+fn check_client(engine: &Engine, tx: &Transaction, before: &Client) -> Result<(), TestCaseError> {
+    let id = tx.client();
+    let client = engine.get_client(&id).unwrap();
+    if before.is_frozen() {
+        prop_assert_eq!(&client, before);
+    }
+    if let Some(view) = engine.get_client_view(&id).unwrap() {
+        prop_assert_eq!(view.available.checked_add(view.held), Ok(view.total));
+    }
+    let mut disputed = Amount::ZERO;
+    for tx_id in &client.disputes {
+        let amount = engine
+            .get_transaction(&(id, *tx_id))
+            .unwrap()
+            .and_then(|tx| tx.amount())
+            .unwrap();
+        disputed = disputed.checked_add(amount).unwrap();
+    }
+    prop_assert_eq!(client.held, disputed);
+    Ok(())
+}
+
+/// Process transactions checking invariants after every one, returns their outcomes.
+fn process_checked(
+    engine: &Engine,
+    transactions: &[Transaction],
+) -> Result<Vec<Outcome>, TestCaseError> {
+    let mut outcomes = Vec::with_capacity(transactions.len());
+    for tx in transactions {
+        let before = engine.get_client(&tx.client()).unwrap();
+        outcomes.push(engine.process_transaction(tx).unwrap());
+        check_client(engine, tx, &before)?;
+    }
+    Ok(outcomes)
+}
+
+proptest! {
+    #[test]
+    fn prop_invariants_hold_after_every_transaction(
+        ops in prop::collection::vec((0..4 as ClientID, any_op()), 1..200),
+        policy in any_policy(),
+    ) {
+        let engine = Engine::builder().with_policy(policy).build();
+        process_checked(&engine, &build_transactions(ops))?;
+    }
+
+    #[test]
+    fn prop_interleaving_of_clients_does_not_matter(
+        clients in prop::collection::vec(prop::collection::vec(any_op(), 0..50), 1..5),
+        picks in prop::collection::vec(any::<Index>(), 200),
+        policy in any_policy(),
+    ) {
+        // Transactions of every client keep their order, with their position as a key.
+        let mut queues: Vec<VecDeque<_>> = clients
+            .into_iter()
+            .enumerate()
+            .map(|(client, ops)| {
+                let ops = ops.into_iter().map(|op| (client as ClientID, op)).collect();
+                build_transactions(ops).into_iter().enumerate().collect()
+            })
+            .collect();
+        let sequential: Vec<_> = queues
+            .iter()
+            .flat_map(|queue| queue.iter().cloned())
+            .collect();
+        let mut interleaved = Vec::with_capacity(sequential.len());
+        let mut picks = picks.into_iter().cycle();
+        while interleaved.len() < sequential.len() {
+            let pending: Vec<_> = (0..queues.len()).filter(|i| !queues[*i].is_empty()).collect();
+            let queue = pending[picks.next().unwrap().index(pending.len())];
+            interleaved.push(queues[queue].pop_front().unwrap());
+        }
+
+        let mut results = vec![];
+        for order in [&sequential, &interleaved] {
+            let engine = Engine::builder().with_policy(policy.clone()).build();
+            let transactions: Vec<_> = order.iter().map(|(_, tx)| tx.clone()).collect();
+            let outcomes = process_checked(&engine, &transactions)?;
+            let mut keyed: Vec<_> = order
+                .iter()
+                .map(|(position, tx)| (tx.client(), *position))
+                .zip(outcomes)
+                .collect();
+            keyed.sort_by_key(|(key, _)| *key);
+            let mut clients = engine.get_all_clients().unwrap();
+            clients.sort_by_key(|client| client.client);
+            results.push((keyed, clients));
+        }
+        prop_assert_eq!(&results[0], &results[1]);
+    }
+}